@@ -1,26 +1,19 @@
 use crate::*;
-use bevy::{ecs::system::Despawn, math::*};
+use bevy::math::*;
 use std::f32::consts::{PI, TAU};
-
+use strum::{EnumCount, IntoEnumIterator};
+
+use crowding::*;
+use economy::FoodDelivered;
+use flow_field::*;
+use jobs::{AssignedJob, JobKind};
+use nest::NestTargets;
+use pheromone::*;
+use rival::RivalColony;
+use weather::Weather;
 use world_map::*;
 
-#[derive(Component)]
-pub struct Pheromone {
-    pub kind: PheromoneKind,
-    pub intensity: f32,
-    pub death_timer: f32,
-}
-
-#[derive(Component)]
-pub struct PheromoneTileGroup(Vec<Entity>);
-
-#[derive(Clone, Copy, Eq, PartialEq)]
-pub enum PheromoneKind {
-    HomeThisWay,
-    FoodThisWay,
-}
-
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
 pub enum AntState {
     Wandering,
     HasFood,
@@ -35,75 +28,112 @@ impl AntState {
     }
 }
 
-#[derive(Bundle)]
-pub struct PheromoneBundle {
-    pheromone: Pheromone,
-    transform: Transform,
-}
-
 #[derive(Bundle)]
 pub struct AntBundle {
     pub ant: Ant,
     pub transform: Transform,
+    pub previous_translation: PreviousTranslation,
+    pub z_level: OnZLevel,
+    pub colony: Colony,
+    pub age: Age,
+    pub needs: Needs,
+    pub health: Health,
+    pub combat: Combat,
     pub rng: EntropyComponent<ChaCha8Rng>,
 }
 
-const PHEROMONE_DECAY_FACTOR: f32 = 0.1;
+pub fn spawn_ant(
+    commands: &mut Commands,
+    rng: &mut GlobalEntropy<ChaCha8Rng>,
+    position: Vec2,
+    z_level: i32,
+    caste: Caste,
+    colony: Colony,
+) -> Entity {
+    let translation = Vec3::new(position.x, position.y, 0.);
+
+    let stats = caste.stats();
+    let mut ant = Ant::from_caste(caste);
+    ant.thresholds = labor::TaskThresholds::randomized(caste, rng);
+
+    commands
+        .spawn(AntBundle {
+            ant,
+            transform: Transform::from_translation(translation),
+            previous_translation: PreviousTranslation(translation),
+            z_level: OnZLevel(z_level),
+            colony,
+            age: Age {
+                age: 0.0,
+                lifespan: stats.lifespan,
+            },
+            needs: Needs::default(),
+            health: Health::new(stats.max_health),
+            combat: Combat::new(stats.attack, stats.defence),
+            rng: rng.fork_rng(),
+        })
+        .id()
+}
+
+pub fn spawn_food(commands: &mut Commands, position: Vec2, z_level: i32, amount: u32) -> Entity {
+    commands
+        .spawn((
+            Transform::from_translation(Vec3::new(position.x, position.y, 1.0))
+                .with_scale(ANT_SIZE),
+            OnZLevel(z_level),
+            Food { amount },
+        ))
+        .id()
+}
 
 pub fn decay_pheromones(
-    mut commands: Commands,
-    mut pheromones: Query<(Entity, &mut Pheromone)>,
+    mut fields: Query<(Ref<ZLevel>, &mut PheromoneField)>,
+    settings: Res<PheromoneSettings>,
+    weather: Res<Weather>,
     time: Res<Time>,
 ) {
-    for (entity, mut pher) in pheromones.iter_mut() {
-        let decay = time.delta_seconds() * PHEROMONE_DECAY_FACTOR;
-        pher.intensity -= decay;
-        pher.death_timer -= decay;
-
-        if pher.death_timer < 0.0 {
-            commands.add(Despawn { entity })
+    for (z_level, mut field) in fields.iter_mut() {
+        // only worked out again when tiles were dug or built since the last tick
+        if z_level.is_changed() {
+            field.set_walkable_tiles(&z_level.walkable_tiles());
         }
-    }
-}
 
-// bounds can exceed [0, tau] range - this is how "which side of the circle are we on?" can be
-// answered
-fn angle_within_bounds(theta: f32, bound_lower: f32, bound_upper: f32) -> bool {
-    !theta.is_nan()
-        && ((theta >= bound_lower && theta <= bound_upper)
-            || (theta >= bound_lower + TAU && theta <= bound_upper + TAU)
-            || (theta >= bound_lower - TAU && theta <= bound_upper - TAU))
-}
-
-fn ant_should_follow(ant_state: AntState, pher_kind: PheromoneKind) -> bool {
-    match (ant_state, pher_kind) {
-        (AntState::Wandering, PheromoneKind::HomeThisWay) => false,
-        (AntState::Wandering, PheromoneKind::FoodThisWay) => true,
-        (AntState::HasFood, PheromoneKind::HomeThisWay) => true,
-        (AntState::HasFood, PheromoneKind::FoodThisWay) => false,
+        for kind in PheromoneKind::iter() {
+            // rain washes trails on the surface away
+            let mut kind_settings = *settings.get(kind);
+            if z_level.z_level == SURFACE_Z_LEVEL {
+                kind_settings.evaporation *= weather.surface_evaporation_factor();
+            }
+            field.update(kind, &kind_settings, time.delta_seconds());
+        }
     }
 }
 
-const RANDOM_WALK_FACTOR: f32 = 0.3;
 const FOOD_HINT_THRESHOLD: f32 = 50.0;
-const HINT_FACTOR: f32 = 0.3;
+
+// Ants smell the field at a fan of points spread across their vision arc, at each of
+// these fractions of their vision range
+const PHEROMONE_SENSOR_COUNT: usize = 5;
+const PHEROMONE_SENSOR_DISTANCES: [f32; 2] = [0.5, 1.0];
 
 fn ant_desired_direction(
     ant: &mut Ant,
     ant_trans: &Transform,
+    colony: Colony,
     rng: &mut EntropyComponent<ChaCha8Rng>,
-    food: &Query<&Transform, (With<Food>, Without<Ant>, Without<Pheromone>)>,
-    z_levels: &Query<&ZLevel>,
-    phers: &Query<(&Transform, &Pheromone), (Without<Ant>, Without<Food>)>,
+    food: &Query<(&Transform, &OnZLevel), (With<Food>, Without<Ant>)>,
+    level: Option<(&ZLevel, &PheromoneField, &FlowFields)>,
+    responses: &PheromoneResponses,
+    job_waypoint: Option<Vec2>,
 ) -> Vec2 {
     let dir_vec = ant_trans.forward().xy();
     let ant_dir = (f32::atan2(dir_vec.y, dir_vec.x) + TAU) % TAU;
 
     let vision_bound_lower = ant_dir - ant.vision_arc / 2.0;
-    let vision_bound_upper = ant_dir + ant.vision_arc / 2.0;
+    let steering = ant.caste.steering();
 
     let angle_offset = rand_uniform_f32(rng) * PI;
-    let length_offset = rand_uniform_f32(rng) + 1.0 / 2.0 * RANDOM_WALK_FACTOR;
+    let length_offset = rand_uniform_f32(rng) + 1.0 / 2.0 * steering.random_walk;
     let random_offset =
         (Quat::from_euler(EulerRot::ZYX, angle_offset, 0.0, 0.0) * (Vec3::X * length_offset)).xy();
 
@@ -112,29 +142,32 @@ fn ant_desired_direction(
     // attractive force from pheromones
 
     let mut cum_dir = ant.secret_desire;
-    let neighborhood = get_local_neighborhood(ant_trans.translation.xy());
-
-    for z_level in z_levels.iter() {
-        for tile_pos in neighborhood.iter() {
-            for pher_ent in z_level[*tile_pos].pher_refs.iter() {
-                if let Ok((pher_trans, pher)) = phers.get(*pher_ent) {
-                    if !ant_should_follow(ant.state, pher.kind) {
-                        continue;
-                    }
 
-                    let to_pher = (pher_trans.translation - ant_trans.translation).xy();
+    if let Some((_, field, _)) = level {
+        let mut weights = [0.0; PheromoneKind::COUNT];
+        for kind in PheromoneKind::iter() {
+            weights[kind as usize] = responses.weight(ant.caste, ant.state, kind);
+        }
 
-                    if to_pher.length() > ant.vision_range {
-                        continue;
-                    }
+        for i_sensor in 0..PHEROMONE_SENSOR_COUNT {
+            let angle = vision_bound_lower
+                + ant.vision_arc * i_sensor as f32 / (PHEROMONE_SENSOR_COUNT - 1) as f32;
+            let sensor_dir = Vec2::from_angle(angle);
 
-                    let angle = (f32::atan2(to_pher.y, to_pher.x) + TAU) % TAU;
+            for distance in PHEROMONE_SENSOR_DISTANCES {
+                let sensor_pos =
+                    ant_trans.translation.xy() + sensor_dir * distance * ant.vision_range;
 
-                    if !angle_within_bounds(angle, vision_bound_lower, vision_bound_upper) {
+                for kind in PheromoneKind::iter() {
+                    let weight = weights[kind as usize];
+                    if weight == 0.0 {
                         continue;
                     }
 
-                    cum_dir += to_pher.normalize() * pher.intensity;
+                    cum_dir += sensor_dir
+                        * field.sample(colony, kind, sensor_pos)
+                        * weight
+                        * steering.pheromone;
                 }
             }
         }
@@ -146,17 +179,39 @@ fn ant_desired_direction(
 
     match ant.state {
         AntState::Wandering => {
-            for food in food.iter() {
+            for (food, food_level) in food.iter() {
+                if Some(food_level.0) != level.map(|(z_level, _, _)| z_level.z_level) {
+                    continue;
+                }
+
                 let to_food = (food.translation - ant_trans.translation).xy();
 
                 if to_food.length() < FOOD_HINT_THRESHOLD {
-                    cum_dir += to_food.normalize() * HINT_FACTOR;
+                    cum_dir += to_food.normalize() * steering.hint;
                 }
             }
         }
         AntState::HasFood => {
-            let to_home = world_map_center() - ant_trans.translation.xy();
-            cum_dir += to_home.normalize() * HINT_FACTOR;
+            // Follows the walkable tiles around walls, unlike a straight line would. Up on
+            // the surface the way home is through one of the colony's entrances.
+            let to_storage = level.and_then(|(z_level, _, flow_fields)| {
+                let target = if z_level.z_level == SURFACE_Z_LEVEL {
+                    FlowTarget::Entrance
+                } else {
+                    FlowTarget::FoodStorage
+                };
+                flow_fields.direction(z_level, colony, target, ant_trans.translation.xy())
+            });
+            if let Some(to_storage) = to_storage {
+                cum_dir += to_storage * steering.hint;
+            }
+        }
+    }
+
+    // Ants on their way to a job mostly ignore everything else
+    if let Some(waypoint) = job_waypoint {
+        if ant.state == AntState::Wandering {
+            cum_dir += (waypoint - ant_trans.translation.xy()).normalize_or_zero() * JOB_STEERING;
         }
     }
 
@@ -174,34 +229,103 @@ fn ant_desired_direction(
 }
 
 const DETECTION_RADIUS: f32 = 20.0;
-const MOMENTUM_WEIGHT: f32 = 1.0;
+const JOB_STEERING: f32 = 2.0;
+const NEED_STEERING: f32 = 2.0;
+const IDLE_STEERING: f32 = 1.0;
+const SEPARATION_WEIGHT: f32 = 1.5;
+// Units of food an ant adds to the stock each time it makes it home
+const FOOD_PER_TRIP: u64 = 1;
+
+// Only ants out foraging have any business on the surface, the rest stay in the nest or
+// head back down into it
+fn wants_surface(ant: &Ant, needs: &Needs) -> bool {
+    ant.state == AntState::Wandering
+        && needs.seeking.is_none()
+        && (ant.caste == Caste::Forager || ant.task == Some(JobKind::Haul))
+}
 
 pub fn update_ant_movement(
     mut ants: Query<
-        (&mut Transform, &mut Ant, &mut EntropyComponent<ChaCha8Rng>),
-        (Without<Food>, Without<Pheromone>),
+        (
+            Entity,
+            &mut Transform,
+            &mut Ant,
+            &mut EntropyComponent<ChaCha8Rng>,
+            &mut OnZLevel,
+            &Colony,
+            &Needs,
+            Option<&mut AssignedJob>,
+        ),
+        Without<Food>,
     >,
-    pheromones: Query<(&Transform, &Pheromone), (Without<Ant>, Without<Food>)>,
-    z_levels: Query<&ZLevel>,
-    food: Query<&Transform, (With<Food>, Without<Ant>, Without<Pheromone>)>,
+    levels: Query<(&ZLevel, &PheromoneField, &FlowFields)>,
+    responses: Res<PheromoneResponses>,
+    nest: Res<NestTargets>,
+    rival: Res<RivalColony>,
+    food: Query<(&Transform, &OnZLevel), (With<Food>, Without<Ant>)>,
+    mut food_sources: Query<(&Transform, &OnZLevel, &mut Food), Without<Ant>>,
+    mut delivered: EventWriter<FoodDelivered>,
+    crowding: Res<SpatialHash>,
+    weather: Res<Weather>,
     time: Res<Time>,
-    z_level_q: Query<&ZLevel>,
 ) {
-    let world_center = world_map_center();
+    for (entity, mut ant_trans, mut ant, mut rng, mut ant_level, colony, needs, assigned_job) in
+        ants.iter_mut()
+    {
+        if needs.is_resting {
+            continue;
+        }
+
+        let level = levels
+            .iter()
+            .find(|(z_level, _, _)| z_level.z_level == ant_level.0);
+        let on_surface = ant_level.0 == SURFACE_Z_LEVEL;
 
-    for (mut ant_trans, mut ant, mut rng) in ants.iter_mut() {
         match ant.state {
             AntState::Wandering => {
-                for food in food.iter() {
-                    if (ant_trans.translation - food.translation).length() <= DETECTION_RADIUS {
+                for (food_trans, food_level, mut food_source) in food_sources.iter_mut() {
+                    // Emptied this tick, despawned once every ant has moved
+                    if food_source.amount == 0 || *food_level != *ant_level {
+                        continue;
+                    }
+
+                    if (ant_trans.translation - food_trans.translation).length() <= DETECTION_RADIUS
+                    {
+                        food_source.amount -= 1;
                         ant.state = AntState::HasFood;
                         ant.secret_desire *= -1.0;
                         ant_trans.rotation *= Quat::from_euler(EulerRot::ZXY, PI, 0.0, 0.0);
+                        break;
                     }
                 }
             }
             AntState::HasFood => {
-                if (ant_trans.translation.xy() - world_center).length() < DETECTION_RADIUS {
+                // food can only be stored underground
+                let position = ant_trans.translation.xy();
+                let at_storage = if *colony == Colony::PLAYER {
+                    (position - nest.drop_off(ant_level.0, position)).length() < DETECTION_RADIUS
+                } else {
+                    // the rival keeps its food wherever it's standing in its nest
+                    let tile = world_pos_to_two_d_index(position);
+                    level
+                        .and_then(|(z_level, _, _)| z_level.building_at(tile))
+                        .map_or(false, |building| {
+                            FlowTarget::FoodStorage.is_goal(
+                                *colony,
+                                &rival,
+                                ant_level.0,
+                                tile,
+                                building,
+                            )
+                        })
+                };
+                if !on_surface && at_storage {
+                    delivered.send(FoodDelivered {
+                        ant: entity,
+                        colony: *colony,
+                        position: ant_trans.translation.xy(),
+                        amount: FOOD_PER_TRIP,
+                    });
                     ant.state = AntState::Wandering;
                     ant.secret_desire *= -1.0;
                     ant_trans.rotation *= Quat::from_euler(EulerRot::ZXY, PI, 0.0, 0.0);
@@ -209,30 +333,83 @@ pub fn update_ant_movement(
             }
         }
 
-        let chosen_dir = Vec3::from((
+        // A hungry or tired ant sees to that before any job, going back underground first
+        let need_direction = needs.seeking.and_then(|need| {
+            level.and_then(|(z_level, _, flow_fields)| {
+                let target = if on_surface {
+                    FlowTarget::Entrance
+                } else {
+                    need.flow_target()
+                };
+                flow_fields.direction(z_level, *colony, target, ant_trans.translation.xy())
+            })
+        });
+        // Idle foragers make for their colony's nearest way out, all the food is on the
+        // surface, and idle nurses for the queen chamber the eggs are laid in
+        let idle_target = if on_surface || assigned_job.is_some() {
+            None
+        } else if wants_surface(&ant, needs) {
+            Some(FlowTarget::Entrance)
+        } else if ant.caste == Caste::Nurse
+            && ant.state == AntState::Wandering
+            && needs.seeking.is_none()
+        {
+            Some(FlowTarget::QueenChamber)
+        } else {
+            None
+        };
+        let idle_direction = idle_target.and_then(|target| {
+            level.and_then(|(z_level, _, flow_fields)| {
+                flow_fields.direction(z_level, *colony, target, ant_trans.translation.xy())
+            })
+        });
+        let job_waypoint = assigned_job
+            .filter(|_| needs.seeking.is_none())
+            .and_then(|mut job| job.next_waypoint(ant_trans.translation.xy()));
+
+        let mut chosen_dir = Vec3::from((
             ant_desired_direction(
                 &mut ant,
                 &ant_trans,
+                *colony,
                 &mut rng,
                 &food,
-                &z_levels,
-                &pheromones,
+                level,
+                &responses,
+                job_waypoint,
             ),
             0.0,
         ));
 
+        if let Some(need_direction) = need_direction {
+            chosen_dir = (chosen_dir + need_direction.extend(0.0) * NEED_STEERING).normalize();
+        }
+        if let Some(idle_direction) = idle_direction {
+            chosen_dir = (chosen_dir + idle_direction.extend(0.0) * IDLE_STEERING).normalize();
+        }
+
         ant.secret_desire = chosen_dir.xy();
 
         let momentum_dir = ant_trans.forward().normalize();
 
-        let mut actual_offset = (chosen_dir + momentum_dir * MOMENTUM_WEIGHT).normalize()
-            * ant.speed
-            * time.delta_seconds();
+        let momentum_weight = ant.caste.steering().momentum;
+        let separation_dir =
+            separation(&crowding, entity, ant_level.0, ant_trans.translation.xy()).extend(0.0);
+        let mut speed =
+            ant.speed * congestion_speed_factor(&crowding, ant_level.0, ant_trans.translation.xy());
+        if on_surface {
+            speed *= weather.surface_speed_factor();
+        }
+        let mut actual_offset =
+            (chosen_dir + momentum_dir * momentum_weight + separation_dir * SEPARATION_WEIGHT)
+                .normalize()
+                * speed
+                * time.delta_seconds();
 
         let potential_position = ant_trans.translation + actual_offset;
         let map_pos_potential = world_pos_to_two_d_index(potential_position.xy());
         let map_pos_current = world_pos_to_two_d_index(ant_trans.translation.xy());
-        if let Some(z_level) = z_level_q.iter().find(|z_level| z_level.z_level == 0) {
+        if let Some((z_level, _, _)) = level {
             let is_potential_tile_walkable = z_level.is_tile_walkable(map_pos_potential);
             let world_map_size = world_map_size();
             if potential_position.x > world_map_size.x
@@ -255,135 +432,237 @@ pub fn update_ant_movement(
         ant_trans.rotation = ant_trans.looking_at(potential_position, Vec3::Z).rotation;
         ant_trans.translation += actual_offset;
 
-        // TODO change ant state based on findings
-    }
-}
+        // Stepping onto a shaft can take the ant up or down to wherever the shaft continues
+        let map_pos_new = world_pos_to_two_d_index(ant_trans.translation.xy());
+        let building_new = if map_pos_new != map_pos_current {
+            level.and_then(|(z_level, _, _)| z_level.building_at(map_pos_new))
+        } else {
+            None
+        };
+        if building_new == Some(BuildingType::Shaft) {
+            let connected_levels: Vec<i32> = [ant_level.0 - 1, ant_level.0 + 1]
+                .into_iter()
+                .filter(|other_level| {
+                    levels.iter().any(|(z_level, _, _)| {
+                        z_level.z_level == *other_level
+                            && z_level.building_at(map_pos_new) == Some(BuildingType::Shaft)
+                    })
+                })
+                .collect();
 
-pub const ANT_POOP_INTERVAL: f32 = 5.0;
-pub const PHER_PROX_DISTANCE: f32 = 5.0;
-pub const PHER_NUDGE_DAMPING: f32 = 0.3;
+            // Staying on this level is as likely as taking any of the ways out
+            let choice = rng.next_u32() as usize % (connected_levels.len() + 1);
+            if let Some(other_level) = connected_levels.get(choice) {
+                ant_level.0 = *other_level;
+            }
+        }
 
-pub fn setup_pher_tiles(mut commands: Commands) {
-    for i in 0..MAP_SIZE.x {
-        for j in 0..MAP_SIZE.y {
-            commands.spawn((MapPos(UVec2::new(i, j)), PheromoneTileGroup(vec![])));
+        // Ants only go through an entrance on purpose, out to forage or back in
+        if building_new == Some(BuildingType::Entrance) {
+            let other_level = entrance_connects_to(ant_level.0).filter(|other_level| {
+                levels.iter().any(|(z_level, _, _)| {
+                    z_level.z_level == *other_level
+                        && z_level.building_at(map_pos_new) == Some(BuildingType::Entrance)
+                })
+            });
+            if let Some(other_level) = other_level {
+                if (other_level == SURFACE_Z_LEVEL) == wants_surface(&ant, needs) {
+                    ant_level.0 = other_level;
+                }
+            }
         }
+
+        // TODO change ant state based on findings
     }
 }
 
+pub const ANT_POOP_INTERVAL: f32 = 5.0;
+const PHEROMONE_DEPOSIT_AMOUNT: f32 = 1.0;
+
 pub fn spawn_pheromones(
-    mut commands: Commands,
-    mut ants: Query<(&Transform, &mut Ant), Without<PheromoneTileGroup>>,
-    mut phers: Query<(&mut Transform, &mut Pheromone), Without<Ant>>,
-    mut z_levels: Query<&mut ZLevel>,
+    mut ants: Query<(&Transform, &OnZLevel, &Colony, &mut Ant)>,
+    mut fields: Query<(&ZLevel, &mut PheromoneField)>,
     time: Res<Time>,
 ) {
-    for (ant_trans, mut ant) in ants.iter_mut() {
+    for (ant_trans, ant_level, colony, mut ant) in ants.iter_mut() {
         if ant.time_until_poop > 0.0 {
             ant.time_until_poop -= time.delta_seconds() * ant.speed;
             continue;
         }
 
-        let neighborhood = get_local_neighborhood(ant_trans.translation.xy());
-
-        for mut z_level in z_levels.iter_mut() {
-            for tile_pos in neighborhood.iter() {
-                let tile_state = &mut z_level[*tile_pos];
-
-                let result = tile_state
-                    .pher_refs
-                    .iter()
-                    .map(|pher_ent| (phers.get(*pher_ent), pher_ent))
-                    .filter_map(|(res, pher_ent)| res.map(|x| (x, pher_ent)).ok())
-                    .filter(|((trans, pher), _)| {
-                        pher.kind == ant.state.pher_to_drop()
-                            && (ant_trans.translation - trans.translation).length()
-                                <= PHER_PROX_DISTANCE
-                    })
-                    .reduce(
-                        |((t_closest, p_closest), e_closest),
-                         ((t_current, p_current), e_current)| {
-                            if (ant_trans.translation - t_closest.translation).length()
-                                > (ant_trans.translation - t_closest.translation).length()
-                            {
-                                ((t_current, p_current), e_current)
-                            } else {
-                                ((t_closest, p_closest), e_closest)
-                            }
-                        },
-                    );
-                let mut found_to_edit = false;
-
-                if let Some((_, closest_ent)) = result {
-                    if let Ok((mut pher_trans, mut pher)) = phers.get_mut(*closest_ent) {
-                        let offset = ant_trans.translation - pher_trans.translation;
-                        pher_trans.translation += (1.0 - (pher.intensity / (pher.intensity + 1.0)))
-                            * offset
-                            * PHER_NUDGE_DAMPING;
-
-                        pher.intensity += 1.0;
-                        pher.death_timer = 1.0;
-
-                        found_to_edit = true;
-                    }
-                }
+        for (_, mut field) in fields
+            .iter_mut()
+            .filter(|(z_level, _)| z_level.z_level == ant_level.0)
+        {
+            field.deposit(
+                *colony,
+                ant.state.pher_to_drop(),
+                ant_trans.translation.xy(),
+                PHEROMONE_DEPOSIT_AMOUNT,
+            );
+        }
 
-                if !found_to_edit {
-                    let pher_ent = commands
-                        .spawn(PheromoneBundle {
-                            pheromone: Pheromone {
-                                kind: ant.state.pher_to_drop(),
-                                intensity: 1.0,
-                                death_timer: 1.0,
-                            },
-                            transform: ant_trans.clone(),
-                        })
-                        .id();
-                    tile_state.pher_refs.push(pher_ent);
-                }
+        ant.time_until_poop = ANT_POOP_INTERVAL;
+    }
+}
+
+const BUILDING_PHEROMONE_RATE: f32 = 2.0;
+const ALARM_PHEROMONE_AMOUNT: f32 = 5.0;
+
+pub fn emit_pheromones(
+    mut fields: Query<(&ZLevel, &mut PheromoneField)>,
+    emitters: Query<(
+        &Transform,
+        &PheromoneEmitter,
+        Option<&OnZLevel>,
+        Option<&Colony>,
+    )>,
+    mut alarms: EventReader<AlarmRaised>,
+    time: Res<Time>,
+) {
+    let alarms: Vec<&AlarmRaised> = alarms.read().collect();
+
+    for (z_level, mut field) in fields.iter_mut() {
+        for (i_tile, tile) in z_level.tiles.iter().enumerate() {
+            if let Some(kind) = tile.building.emitted_pheromone() {
+                let tile_center = (one_d_index_to_two_d_index(i_tile).as_vec2() + 0.5) * TILE_SIZE;
+                // only the player builds, so buildings are always the player's
+                field.deposit(
+                    Colony::PLAYER,
+                    kind,
+                    tile_center,
+                    BUILDING_PHEROMONE_RATE * time.delta_seconds(),
+                );
             }
         }
 
-        ant.time_until_poop = ANT_POOP_INTERVAL;
+        for (trans, emitter, emitter_level, emitter_colony) in emitters.iter() {
+            if emitter_level.copied().unwrap_or_default().0 != z_level.z_level {
+                continue;
+            }
+
+            field.deposit(
+                emitter_colony.copied().unwrap_or_default(),
+                emitter.kind,
+                trans.translation.xy(),
+                emitter.rate * time.delta_seconds(),
+            );
+        }
+
+        for alarm in alarms
+            .iter()
+            .filter(|alarm| alarm.z_level == z_level.z_level)
+        {
+            field.deposit(
+                alarm.colony,
+                PheromoneKind::Alarm,
+                alarm.position,
+                ALARM_PHEROMONE_AMOUNT,
+            );
+        }
+    }
+}
+
+pub fn record_previous_translation(mut query: Query<(&Transform, &mut PreviousTranslation)>) {
+    for (trans, mut previous) in query.iter_mut() {
+        previous.0 = trans.translation;
+    }
+}
+
+// Position between the last two simulation ticks matching how far the fixed clock has
+// run ahead of the latest one
+pub fn interpolated_translation(
+    trans: &Transform,
+    previous: &PreviousTranslation,
+    fixed_time: &Time<Fixed>,
+) -> Vec3 {
+    previous
+        .0
+        .lerp(trans.translation, fixed_time.overstep_percentage())
+}
+
+// Runs after transform propagation so whatever is drawn from `GlobalTransform` is
+// smoothed without touching the simulation's `Transform`
+pub fn interpolate_rendered_transforms(
+    mut query: Query<(&Transform, &PreviousTranslation, &mut GlobalTransform)>,
+    fixed_time: Res<Time<Fixed>>,
+) {
+    for (trans, previous, mut global_trans) in query.iter_mut() {
+        let mut rendered = *trans;
+        rendered.translation = interpolated_translation(trans, previous, &fixed_time);
+        *global_trans = GlobalTransform::from(rendered);
     }
 }
 
-pub fn debug_ants(ants: Query<(&Ant, &Transform)>, mut gizmos: Gizmos) {
-    for (ant, ant_trans) in ants.iter() {
+pub fn debug_ants(
+    ants: Query<(&Ant, &Transform, &PreviousTranslation)>,
+    fixed_time: Res<Time<Fixed>>,
+    mut gizmos: Gizmos,
+) {
+    for (ant, ant_trans, previous) in ants.iter() {
         let facing = ant.secret_desire;
+        let position = interpolated_translation(ant_trans, previous, &fixed_time).xy();
 
-        let start = ant_trans.translation.xy();
-        let end = ant_trans.translation.xy() + facing.normalize() * ant.vision_range;
+        let start = position;
+        let end = position + facing.normalize() * ant.vision_range;
 
         gizmos.line_2d(start.xy(), end.xy(), Color::WHITE);
         gizmos
-            .circle_2d(ant_trans.translation.xy(), ant.vision_range, Color::WHITE)
+            .circle_2d(position, ant.vision_range, Color::WHITE)
             .segments(16);
     }
 }
 
-pub fn debug_ants_minimal(ants: Query<(&Ant, &Transform)>, mut gizmos: Gizmos) {
-    for (ant, ant_trans) in ants.iter() {
+pub fn debug_ants_minimal(
+    ants: Query<(&Ant, &Transform, &PreviousTranslation, &OnZLevel)>,
+    selected_z_level_q: Query<&SelectedZLevel>,
+    fixed_time: Res<Time<Fixed>>,
+    mut gizmos: Gizmos,
+) {
+    let selected_z_level = selected_z_level_q.single();
+
+    for (ant, ant_trans, previous, ant_level) in ants.iter() {
+        if ant_level.0 != selected_z_level.0 {
+            continue;
+        }
+
         let facing = ant.secret_desire.normalize() * 2.0;
+        let position = interpolated_translation(ant_trans, previous, &fixed_time).xy();
 
-        let start = ant_trans.translation.xy() - facing;
-        let end = ant_trans.translation.xy() + facing;
+        let start = position - facing;
+        let end = position + facing;
 
         gizmos.line_2d(start.xy(), end.xy(), Color::BLACK);
     }
 }
 
-pub fn debug_phers(phers: Query<(&Pheromone, &Transform)>, mut gizmos: Gizmos) {
-    for (pher, pher_trans) in phers.iter() {
-        gizmos
-            .circle_2d(
-                pher_trans.translation.xy(),
-                1.0,
-                match pher.kind {
-                    PheromoneKind::HomeThisWay => Color::BLUE,
-                    PheromoneKind::FoodThisWay => Color::GREEN,
-                } * pher.intensity,
-            )
-            .segments(8);
+pub fn debug_phers(
+    fields: Query<(&ZLevel, &PheromoneField)>,
+    selected_z_level_q: Query<&SelectedZLevel>,
+    mut gizmos: Gizmos,
+) {
+    let selected_z_level = selected_z_level_q.single();
+
+    for (_, field) in fields
+        .iter()
+        .filter(|(z_level, _)| z_level.z_level == selected_z_level.0)
+    {
+        for kind in PheromoneKind::iter() {
+            let color = match kind {
+                PheromoneKind::HomeThisWay => Color::BLUE,
+                PheromoneKind::FoodThisWay => Color::GREEN,
+                PheromoneKind::Alarm => Color::RED,
+                PheromoneKind::RecruitDig => Color::YELLOW,
+                PheromoneKind::QueenPresence => Color::PURPLE,
+            };
+
+            for colony in Colony::all() {
+                for (cell_pos, intensity) in field.iter_cells(colony, kind) {
+                    gizmos
+                        .circle_2d(cell_pos, 1.0, color * intensity)
+                        .segments(8);
+                }
+            }
+        }
     }
 }
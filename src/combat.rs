@@ -0,0 +1,190 @@
+use bevy::prelude::*;
+
+use crate::components::*;
+use crate::crowding::*;
+use crate::enemy::Bug;
+use crate::jobs::*;
+use crate::lifecycle::spawn_corpse;
+use crate::pheromone::AlarmRaised;
+
+/// Running totals of who died fighting, for scoring defence scenarios.
+#[derive(Resource, Default, Debug)]
+pub struct CombatLog {
+    pub ants_killed: u32,
+    pub bugs_killed: u32,
+}
+
+// Fighters this close can hit each other, can't exceed the spatial hash's cell size
+const MELEE_RANGE: f32 = 6.0;
+// Seconds between strikes
+const ATTACK_COOLDOWN: f32 = 1.0;
+// Soldiers are sent after whatever hits the colony, ahead of most other work
+const DEFEND_JOB_PRIORITY: u32 = 4;
+
+// Damage of a single strike, never more than `attack` and shrinking as `defence` grows
+pub fn melee_damage(attack: f32, defence: f32) -> f32 {
+    attack * attack / (attack + defence).max(f32::EPSILON)
+}
+
+// Every bug trades blows with the ants around it, the queen included. Any ant hit raises
+// its colony's alarm, and the player's colony also puts out a job to fight the bug that
+// did it.
+pub fn resolve_melee(
+    mut bugs: Query<(Entity, &Transform, &OnZLevel, &mut Health, &mut Combat), With<Bug>>,
+    mut ants: Query<(Option<&Colony>, &mut Health, &mut Combat), Without<Bug>>,
+    mut board: ResMut<JobBoard>,
+    mut alarms: EventWriter<AlarmRaised>,
+    hash: Res<SpatialHash>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+
+    for (bug, bug_trans, bug_level, mut bug_health, mut bug_combat) in bugs.iter_mut() {
+        let bug_pos = bug_trans.translation.xy();
+        let mut nearby: Vec<(Entity, Vec2)> =
+            hash.neighbours(bug_level.0, bug_pos, MELEE_RANGE).collect();
+        nearby.sort_by(|(_, a), (_, b)| {
+            a.distance_squared(bug_pos)
+                .total_cmp(&b.distance_squared(bug_pos))
+        });
+
+        let mut hit_at = None;
+        if bug_combat.next_attack_at <= now {
+            if let Some((ant, ant_pos)) = nearby.first() {
+                if let Ok((ant_colony, mut ant_health, ant_combat)) = ants.get_mut(*ant) {
+                    ant_health.current -= melee_damage(bug_combat.attack, ant_combat.defence);
+                    hit_at = Some((*ant_pos, ant_colony.copied().unwrap_or_default()));
+                }
+            }
+        }
+
+        if let Some((position, colony)) = hit_at {
+            bug_combat.next_attack_at = now + ATTACK_COOLDOWN;
+            alarms.send(AlarmRaised {
+                position,
+                z_level: bug_level.0,
+                colony,
+            });
+            if colony == Colony::PLAYER && !board.has_job_for(bug, JobKind::Guard) {
+                board.post(
+                    JobKind::Guard,
+                    DEFEND_JOB_PRIORITY,
+                    bug_level.0,
+                    bug_pos,
+                    Some(bug),
+                );
+            }
+        }
+
+        for (ant, _) in nearby {
+            if let Ok((_, _, mut ant_combat)) = ants.get_mut(ant) {
+                strike(&mut ant_combat, &mut bug_health, bug_combat.defence, now);
+            }
+        }
+    }
+}
+
+// Ants of different colonies that run into each other fight. Works outward from the
+// rival's ants as there are far fewer of them.
+pub fn resolve_skirmishes(
+    mut ants: Query<
+        (
+            Entity,
+            &Transform,
+            &OnZLevel,
+            &Colony,
+            &mut Health,
+            &mut Combat,
+        ),
+        With<Ant>,
+    >,
+    mut board: ResMut<JobBoard>,
+    mut alarms: EventWriter<AlarmRaised>,
+    hash: Res<SpatialHash>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    let rivals: Vec<(Entity, Vec2, i32)> = ants
+        .iter()
+        .filter(|(_, _, _, colony, _, _)| **colony != Colony::PLAYER)
+        .map(|(entity, trans, level, _, _, _)| (entity, trans.translation.xy(), level.0))
+        .collect();
+
+    for (rival, rival_pos, z_level) in rivals {
+        for (other, other_pos) in hash.neighbours(z_level, rival_pos, MELEE_RANGE) {
+            let Ok([rival_ant, other_ant]) = ants.get_many_mut([rival, other]) else {
+                continue;
+            };
+            let (_, _, _, rival_colony, mut rival_health, mut rival_combat) = rival_ant;
+            let (_, _, _, other_colony, mut other_health, mut other_combat) = other_ant;
+            if rival_colony == other_colony {
+                continue;
+            }
+
+            // (where, whose ant was hit, by whom)
+            let mut hits = Vec::new();
+            if strike(
+                &mut rival_combat,
+                &mut other_health,
+                other_combat.defence,
+                now,
+            ) {
+                hits.push((other_pos, *other_colony, rival));
+            }
+            if strike(
+                &mut other_combat,
+                &mut rival_health,
+                rival_combat.defence,
+                now,
+            ) {
+                hits.push((rival_pos, *rival_colony, other));
+            }
+
+            for (position, colony, attacker) in hits {
+                alarms.send(AlarmRaised {
+                    position,
+                    z_level,
+                    colony,
+                });
+                if colony == Colony::PLAYER && !board.has_job_for(attacker, JobKind::Guard) {
+                    board.post(
+                        JobKind::Guard,
+                        DEFEND_JOB_PRIORITY,
+                        z_level,
+                        position,
+                        Some(attacker),
+                    );
+                }
+            }
+        }
+    }
+}
+
+// Lands a blow if the attacker is ready to, returns whether it did
+fn strike(attacker: &mut Combat, defender: &mut Health, defence: f32, now: f32) -> bool {
+    if attacker.attack <= 0.0 || attacker.next_attack_at > now {
+        return false;
+    }
+    defender.current -= melee_damage(attacker.attack, defence);
+    attacker.next_attack_at = now + ATTACK_COOLDOWN;
+    true
+}
+
+pub fn remove_dead(
+    mut commands: Commands,
+    fighters: Query<(Entity, &Transform, &OnZLevel, &Health, Option<&Bug>)>,
+    mut log: ResMut<CombatLog>,
+) {
+    for (entity, trans, level, health, bug) in fighters.iter() {
+        if health.current > 0.0 {
+            continue;
+        }
+
+        match bug {
+            Some(_) => log.bugs_killed += 1,
+            None => log.ants_killed += 1,
+        }
+        commands.entity(entity).despawn();
+        spawn_corpse(&mut commands, trans.translation.xy(), level.0);
+    }
+}
@@ -1,35 +1,278 @@
 use bevy::prelude::*;
+use strum_macros::EnumIter;
 
 use crate::behavior::*;
+use crate::jobs::JobKind;
+use crate::labor::TaskThresholds;
 
 #[derive(Component)]
 pub struct Player;
 
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, EnumIter)]
+pub enum Caste {
+    Worker,
+    Forager,
+    Soldier,
+    Nurse,
+    Digger,
+}
+
+pub struct CasteStats {
+    pub speed: f32,
+    pub vision_range: f32,
+    pub vision_arc: f32, // in radians
+    pub lifespan: f32,   // seconds as an adult before dying of old age
+    pub max_health: f32,
+    pub attack: f32,
+    pub defence: f32,
+}
+
+// Weights `ant_desired_direction` uses when blending its steering forces
+pub struct SteeringProfile {
+    pub random_walk: f32, // how much the ant wanders off on its own
+    pub pheromone: f32,   // how strongly it follows what it smells
+    pub hint: f32,        // how strongly it heads for food/home it knows about
+    pub momentum: f32,    // how much it keeps going the way it's facing
+}
+
+impl Caste {
+    pub fn stats(&self) -> CasteStats {
+        match self {
+            Caste::Worker => CasteStats {
+                speed: 50.0,
+                vision_range: 20.0,
+                vision_arc: 1.5,
+                lifespan: 600.0,
+                max_health: 10.0,
+                attack: 2.0,
+                defence: 1.0,
+            },
+            Caste::Forager => CasteStats {
+                speed: 65.0,
+                vision_range: 30.0,
+                vision_arc: 2.0,
+                lifespan: 400.0,
+                max_health: 8.0,
+                attack: 1.5,
+                defence: 0.5,
+            },
+            Caste::Soldier => CasteStats {
+                speed: 40.0,
+                vision_range: 25.0,
+                vision_arc: 1.5,
+                lifespan: 500.0,
+                max_health: 25.0,
+                attack: 6.0,
+                defence: 4.0,
+            },
+            Caste::Nurse => CasteStats {
+                speed: 35.0,
+                vision_range: 15.0,
+                vision_arc: 1.5,
+                lifespan: 700.0,
+                max_health: 8.0,
+                attack: 1.0,
+                defence: 0.5,
+            },
+            Caste::Digger => CasteStats {
+                speed: 40.0,
+                vision_range: 15.0,
+                vision_arc: 1.0,
+                lifespan: 500.0,
+                max_health: 12.0,
+                attack: 3.0,
+                defence: 2.0,
+            },
+        }
+    }
+
+    pub fn steering(&self) -> SteeringProfile {
+        match self {
+            Caste::Worker => SteeringProfile {
+                random_walk: 0.3,
+                pheromone: 1.0,
+                hint: 0.3,
+                momentum: 1.0,
+            },
+            Caste::Forager => SteeringProfile {
+                random_walk: 0.5,
+                pheromone: 1.2,
+                hint: 0.5,
+                momentum: 1.0,
+            },
+            Caste::Soldier => SteeringProfile {
+                random_walk: 0.2,
+                pheromone: 1.0,
+                hint: 0.2,
+                momentum: 1.5,
+            },
+            Caste::Nurse => SteeringProfile {
+                random_walk: 0.1,
+                pheromone: 0.5,
+                hint: 0.3,
+                momentum: 0.5,
+            },
+            Caste::Digger => SteeringProfile {
+                random_walk: 0.2,
+                pheromone: 0.8,
+                hint: 0.3,
+                momentum: 2.0,
+            },
+        }
+    }
+
+    // Frame in Ants.png. Every row there is one facing and every colour a block of walk
+    // frames along it, all castes are taken from the rows facing up as the sprites are
+    // turned from there. Diggers get the ant carrying something.
+    pub fn atlas_index(&self) -> usize {
+        let (row, variant) = match self {
+            Caste::Worker => (ANT_ATLAS_UP_ROW, 0),
+            Caste::Forager => (ANT_ATLAS_UP_ROW, 1),
+            Caste::Soldier => (ANT_ATLAS_UP_ROW, 2),
+            Caste::Nurse => (ANT_ATLAS_UP_ROW, 3),
+            Caste::Digger => (ANT_ATLAS_CARRYING_UP_ROW, 0),
+        };
+        row * ANT_ATLAS_COLUMNS + variant * ANT_ATLAS_VARIANT_FRAMES
+    }
+}
+
+// Layout of Ants.png
+const ANT_ATLAS_COLUMNS: usize = 12;
+const ANT_ATLAS_VARIANT_FRAMES: usize = 3;
+const ANT_ATLAS_UP_ROW: usize = 3;
+const ANT_ATLAS_CARRYING_UP_ROW: usize = 7;
+
 #[derive(Component)]
 pub struct Ant {
+    pub caste: Caste,
     pub state: AntState,
     pub speed: f32,
     pub vision_range: f32,
     pub vision_arc: f32, // in radians
     pub time_until_poop: f32,
     pub secret_desire: Vec2,
+    pub task: Option<JobKind>, // taken up once the task's stimulus passes the threshold
+    pub thresholds: TaskThresholds,
 }
 
-impl Default for Ant {
-    fn default() -> Self {
+impl Ant {
+    pub fn from_caste(caste: Caste) -> Self {
+        let stats = caste.stats();
         Ant {
+            caste,
             state: AntState::Wandering,
-            speed: 50.0,
-            vision_range: 20.0,
-            vision_arc: 1.5,
+            speed: stats.speed,
+            vision_range: stats.vision_range,
+            vision_arc: stats.vision_arc,
             time_until_poop: ANT_POOP_INTERVAL,
             secret_desire: Vec2::ZERO,
+            task: None,
+            thresholds: TaskThresholds::for_caste(caste),
+        }
+    }
+}
+
+impl Default for Ant {
+    fn default() -> Self {
+        Ant::from_caste(Caste::Worker)
+    }
+}
+
+// Seconds lived as an adult, the ant dies of old age once it reaches `lifespan`
+#[derive(Component)]
+pub struct Age {
+    pub age: f32,
+    pub lifespan: f32,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum Need {
+    Food, // off to eat at a food storage
+    Rest, // off to rest in a chamber
+}
+
+/// How fed and rested an ant is, both from 1 (fully) down to 0. An ant that runs out of
+/// both dies.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Needs {
+    pub satiety: f32,
+    pub energy: f32,
+    pub seeking: Option<Need>,
+    pub is_resting: bool,
+}
+
+impl Default for Needs {
+    fn default() -> Self {
+        Self {
+            satiety: 1.0,
+            energy: 1.0,
+            seeking: None,
+            is_resting: false,
         }
     }
 }
 
+// Hit points left, the entity dies once they reach 0
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+}
+
+/// Melee stats of anything that fights, ants and intruders alike.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Combat {
+    pub attack: f32,
+    pub defence: f32,
+    pub next_attack_at: f32, // elapsed seconds, it can't strike again before then
+}
+
+impl Combat {
+    pub fn new(attack: f32, defence: f32) -> Self {
+        Self {
+            attack,
+            defence,
+            next_attack_at: 0.0,
+        }
+    }
+}
+
+// Where the entity was at the start of the latest simulation tick, so rendering can
+// interpolate between ticks
+#[derive(Component, Default)]
+pub struct PreviousTranslation(pub Vec3);
+
+// The `ZLevel` an entity is on
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct OnZLevel(pub i32);
+
+pub const COLONY_COUNT: usize = 2;
+
+/// Which colony an ant, or whatever it leaves behind, belongs to. Anything without one
+/// belongs to the player's.
+#[derive(Component, Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub struct Colony(pub u8);
+
+impl Colony {
+    pub const PLAYER: Colony = Colony(0);
+    pub const RIVAL: Colony = Colony(1);
+
+    pub fn all() -> impl Iterator<Item = Colony> {
+        (0..COLONY_COUNT as u8).map(Colony)
+    }
+}
+
 #[derive(Component)]
 pub struct GameCamera;
 
+/// A food source, ants take one unit each time they pick from it. Despawns once empty.
 #[derive(Component)]
-pub struct Food;
+pub struct Food {
+    pub amount: u32,
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::components::*;
+use crate::queen::Queen;
+use crate::world_map::*;
+
+// Side of a spatial hash cell, at least as big as the largest radius ever queried
+const CELL_SIZE: f32 = 8.0;
+
+/// Every ant's position as of the start of the tick, the queen's too, bucketed by level
+/// and cell so nearby ants can be found without checking all of them. Also counts the
+/// ants on each tile for the congestion model.
+#[derive(Resource, Default)]
+pub struct SpatialHash {
+    cells: HashMap<(i32, IVec2), Vec<(Entity, Vec2)>>,
+    tile_counts: HashMap<(i32, UVec2), u32>,
+}
+
+impl SpatialHash {
+    fn cell(world_pos: Vec2) -> IVec2 {
+        (world_pos / CELL_SIZE).floor().as_ivec2()
+    }
+
+    // Ants within `radius` of `world_pos` on `z_level`, `radius` can't exceed `CELL_SIZE`
+    pub fn neighbours(
+        &self,
+        z_level: i32,
+        world_pos: Vec2,
+        radius: f32,
+    ) -> impl Iterator<Item = (Entity, Vec2)> + '_ {
+        let center = Self::cell(world_pos);
+        (-1..=1)
+            .flat_map(move |y| (-1..=1).map(move |x| center + IVec2::new(x, y)))
+            .filter_map(move |cell| self.cells.get(&(z_level, cell)))
+            .flatten()
+            .copied()
+            .filter(move |(_, other_pos)| other_pos.distance_squared(world_pos) <= radius * radius)
+    }
+
+    pub fn ants_on_tile(&self, z_level: i32, tile: UVec2) -> u32 {
+        self.tile_counts.get(&(z_level, tile)).copied().unwrap_or(0)
+    }
+}
+
+pub fn rebuild_spatial_hash(
+    mut hash: ResMut<SpatialHash>,
+    ants: Query<(Entity, &Transform, &OnZLevel), Or<(With<Ant>, With<Queen>)>>,
+) {
+    hash.cells.clear();
+    hash.tile_counts.clear();
+
+    for (entity, ant_trans, ant_level) in ants.iter() {
+        let position = ant_trans.translation.xy();
+        hash.cells
+            .entry((ant_level.0, SpatialHash::cell(position)))
+            .or_default()
+            .push((entity, position));
+
+        if position.x >= 0. && position.y >= 0. {
+            *hash
+                .tile_counts
+                .entry((ant_level.0, world_pos_to_two_d_index(position)))
+                .or_default() += 1;
+        }
+    }
+}
+
+// Ants closer than this push each other apart
+pub const SEPARATION_RADIUS: f32 = 5.0;
+// Ants a tile holds before it starts slowing them down
+const TILE_CAPACITY: u32 = 6;
+// Fraction of speed lost for every ant over capacity
+const CONGESTION_SLOWDOWN: f32 = 0.1;
+const MIN_CONGESTED_SPEED: f32 = 0.2;
+
+// Direction away from the ants crowding `entity`, stronger the closer they are
+pub fn separation(hash: &SpatialHash, entity: Entity, z_level: i32, world_pos: Vec2) -> Vec2 {
+    hash.neighbours(z_level, world_pos, SEPARATION_RADIUS)
+        .filter(|(other, _)| *other != entity)
+        .map(|(_, other_pos)| {
+            let away = world_pos - other_pos;
+            let distance = away.length();
+            if distance < 1e-3 {
+                // exactly on top of each other, split them along something stable
+                Vec2::from_angle(entity.index() as f32)
+            } else {
+                away / distance * (1.0 - distance / SEPARATION_RADIUS)
+            }
+        })
+        .sum()
+}
+
+// Fraction of its normal speed an ant keeps on a tile this crowded
+pub fn congestion_speed_factor(hash: &SpatialHash, z_level: i32, world_pos: Vec2) -> f32 {
+    if world_pos.x < 0. || world_pos.y < 0. {
+        return 1.0;
+    }
+    let count = hash.ants_on_tile(z_level, world_pos_to_two_d_index(world_pos));
+    let over_capacity = count.saturating_sub(TILE_CAPACITY) as f32;
+    (1.0 / (1.0 + over_capacity * CONGESTION_SLOWDOWN)).max(MIN_CONGESTED_SPEED)
+}
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::components::*;
+use crate::jobs::*;
+use crate::pheromone::*;
+use crate::world_map::*;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum DesignationKind {
+    Excavate,  // digging a building out of solid soil
+    Construct, // turning one building into another, or filling it back in
+}
+
+impl DesignationKind {
+    // Ant-seconds of work needed to finish
+    pub fn work_required(&self) -> f32 {
+        match self {
+            DesignationKind::Excavate => 10.0,
+            DesignationKind::Construct => 5.0,
+        }
+    }
+}
+
+/// A tile the player wants turned into `building`. Ants have to reach it and put
+/// `work_required` ant-seconds into it, tracked by `progress` going up to 1, before the
+/// tile actually changes.
+#[derive(Component)]
+pub struct Designation {
+    pub z_level: i32,
+    pub tile: UVec2,
+    pub building: BuildingType,
+    pub kind: DesignationKind,
+    pub work_required: f32, // more than the kind's usual through clay
+    pub progress: f32,
+}
+
+/// Asks for `tile` on `z_level` to be turned into `building`, replacing any designation
+/// already there.
+#[derive(Event)]
+pub struct Designate {
+    pub z_level: i32,
+    pub tile: UVec2,
+    pub building: BuildingType,
+}
+
+#[derive(Event)]
+pub struct CancelDesignation {
+    pub z_level: i32,
+    pub tile: UVec2,
+}
+
+/// The designation entity on each tile, keyed by level and tile.
+#[derive(Resource, Default)]
+pub struct Designations(pub HashMap<(i32, UVec2), Entity>);
+
+// Ants this close to a designated tile's center can work on it, enough to reach it
+// from a neighbouring tile
+const WORK_RADIUS: f32 = 20.0;
+const RECRUIT_DIG_RATE: f32 = 3.0;
+const DIG_JOB_PRIORITY: u32 = 2;
+
+// Work each caste puts into a designation per second
+fn work_rate(caste: Caste) -> f32 {
+    match caste {
+        Caste::Digger => 1.0,
+        Caste::Worker => 0.5,
+        _ => 0.0,
+    }
+}
+
+pub fn apply_designation_requests(
+    mut commands: Commands,
+    mut designations: ResMut<Designations>,
+    mut board: ResMut<JobBoard>,
+    designation_q: Query<&Designation>,
+    z_levels: Query<&ZLevel>,
+    mut designate: EventReader<Designate>,
+    mut cancel: EventReader<CancelDesignation>,
+) {
+    for request in cancel.read() {
+        if let Some(entity) = designations.0.remove(&(request.z_level, request.tile)) {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    for request in designate.read() {
+        if !request.building.is_placeable_on(request.z_level) {
+            continue;
+        }
+        let Some(z_level) = z_levels
+            .iter()
+            .find(|z_level| z_level.z_level == request.z_level)
+        else {
+            continue;
+        };
+        let Some(current) = z_level.building_at(request.tile) else {
+            continue;
+        };
+        let terrain = z_level[request.tile].terrain;
+
+        let key = (request.z_level, request.tile);
+        if let Some(entity) = designations.0.get(&key) {
+            if designation_q.get(*entity).map_or(false, |designation| {
+                designation.building == request.building
+            }) {
+                continue;
+            }
+            commands.entity(*entity).despawn();
+            designations.0.remove(&key);
+        }
+
+        // entrances only lead between the surface and the level right below it, and only
+        // where there's something to dig through on the way
+        let entrance_blocked = request.building == BuildingType::Entrance
+            && !entrance_connects_to(request.z_level).map_or(false, |other_level| {
+                z_levels.iter().any(|z_level| {
+                    z_level.z_level == other_level
+                        && (z_level.is_tile_diggable(request.tile)
+                            || z_level.building_at(request.tile) != Some(BuildingType::None))
+                })
+            });
+        if current == request.building || entrance_blocked {
+            continue;
+        }
+
+        let (kind, work_required) = if current == BuildingType::None {
+            // rock and water can't be dug at all
+            let Some(factor) = terrain.dig_work_factor() else {
+                continue;
+            };
+            (
+                DesignationKind::Excavate,
+                DesignationKind::Excavate.work_required() * factor,
+            )
+        } else {
+            (
+                DesignationKind::Construct,
+                DesignationKind::Construct.work_required(),
+            )
+        };
+
+        let work_position = recruit_position(z_level, request.tile);
+        let entity = commands
+            .spawn((
+                Transform::from_translation(work_position.extend(0.0)),
+                OnZLevel(request.z_level),
+                PheromoneEmitter {
+                    kind: PheromoneKind::RecruitDig,
+                    rate: RECRUIT_DIG_RATE,
+                },
+                Designation {
+                    z_level: request.z_level,
+                    tile: request.tile,
+                    building: request.building,
+                    kind,
+                    work_required,
+                    progress: 0.0,
+                },
+            ))
+            .id();
+        designations.0.insert(key, entity);
+        board.post(
+            JobKind::Dig,
+            DIG_JOB_PRIORITY,
+            request.z_level,
+            work_position,
+            Some(entity),
+        );
+    }
+}
+
+// Pheromone can't spread out of solid soil, so recruit from a walkable neighbour when
+// there is one
+fn recruit_position(z_level: &ZLevel, tile: UVec2) -> Vec2 {
+    let neighbour = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+        .into_iter()
+        .map(|offset| tile.as_ivec2() + offset)
+        .filter(|neighbour| neighbour.x >= 0 && neighbour.y >= 0)
+        .map(|neighbour| neighbour.as_uvec2())
+        .find(|neighbour| z_level.is_tile_walkable(*neighbour));
+
+    (neighbour.unwrap_or(tile).as_vec2() + 0.5) * TILE_SIZE
+}
+
+pub fn work_designations(
+    mut commands: Commands,
+    mut designations: ResMut<Designations>,
+    mut designation_q: Query<(Entity, &mut Designation)>,
+    ants: Query<(&Transform, &OnZLevel, &Colony, &Ant)>,
+    mut z_levels: Query<&mut ZLevel>,
+    mut tiles_changed: EventWriter<TilesChanged>,
+    time: Res<Time>,
+) {
+    for (entity, mut designation) in designation_q.iter_mut() {
+        let tile_center = (designation.tile.as_vec2() + 0.5) * TILE_SIZE;
+        let work: f32 = ants
+            .iter()
+            .filter(|(ant_trans, ant_level, colony, _)| {
+                **colony == Colony::PLAYER
+                    && ant_level.0 == designation.z_level
+                    && (ant_trans.translation.xy() - tile_center).length() <= WORK_RADIUS
+            })
+            .map(|(_, _, _, ant)| work_rate(ant.caste))
+            .sum();
+        if work == 0.0 {
+            continue;
+        }
+
+        designation.progress += work * time.delta_seconds() / designation.work_required;
+        if designation.progress < 1.0 {
+            continue;
+        }
+
+        if designation.building == BuildingType::Entrance {
+            build_entrance(&mut z_levels, &mut tiles_changed, designation.tile);
+        } else if let Some(mut z_level) = z_levels
+            .iter_mut()
+            .find(|z_level| z_level.z_level == designation.z_level)
+        {
+            z_level[designation.tile].building = designation.building;
+            tiles_changed.send(TilesChanged {
+                z_level: designation.z_level,
+                tiles: vec![designation.tile],
+            });
+        }
+
+        designations
+            .0
+            .remove(&(designation.z_level, designation.tile));
+        commands.entity(entity).despawn();
+    }
+}
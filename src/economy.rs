@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+
+use crate::components::*;
+use crate::resources::*;
+use crate::rival::RivalColony;
+
+/// Sent whenever an ant drops food off at its colony's nest.
+#[derive(Event)]
+pub struct FoodDelivered {
+    pub ant: Entity,
+    pub colony: Colony,
+    pub position: Vec2,
+    pub amount: u64,
+}
+
+/// Food the colony has eaten but not yet taken out of `FoodRes`, which only holds whole
+/// units. The colony is starving whenever it owes more than the stock can pay.
+#[derive(Resource, Default)]
+pub struct FoodUpkeep {
+    pub owed: f32,
+    pub is_starving: bool,
+}
+
+impl FoodUpkeep {
+    pub fn charge(&mut self, amount: f32) {
+        self.owed += amount;
+    }
+
+    // Pays off as many whole units as `food` holds
+    pub fn pay(&mut self, food: &mut FoodRes) {
+        let whole_units = self.owed.floor();
+        let paid = whole_units.min(food.amount() as f32);
+        if paid > 0.0 && food.try_consume(paid as u64) {
+            self.owed -= paid;
+        }
+
+        self.is_starving = self.owed >= 1.0;
+    }
+}
+
+// What an ant eats on average, it's charged a meal at a time whenever it eats
+pub const ANT_FOOD_PER_SECOND: f32 = 0.0005;
+pub const LARVA_FOOD_PER_SECOND: f32 = 0.05;
+// Starving ants age this many times faster
+pub const STARVATION_AGEING_FACTOR: f32 = 5.0;
+
+pub fn collect_deliveries(
+    mut food: ResMut<FoodRes>,
+    mut rival: ResMut<RivalColony>,
+    mut delivered: EventReader<FoodDelivered>,
+) {
+    for delivery in delivered.read() {
+        if delivery.colony == Colony::PLAYER {
+            food.add(delivery.amount);
+        } else {
+            rival.food.add(delivery.amount);
+        }
+    }
+}
+
+// Takes whatever the ants and larvae have eaten out of the stock
+pub fn colony_upkeep(mut food: ResMut<FoodRes>, mut upkeep: ResMut<FoodUpkeep>) {
+    upkeep.pay(&mut food);
+}
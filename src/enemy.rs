@@ -0,0 +1,293 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use bevy::prelude::*;
+use bevy_prng::ChaCha8Rng;
+use bevy_rand::prelude::*;
+use rand_core::RngCore;
+
+use crate::components::*;
+use crate::pheromone::*;
+use crate::util::*;
+use crate::world_map::*;
+
+/// Kinds of bug differ in their stats only. Bugs.png holds nothing but beetles in four
+/// colours, so spiders and centipedes are drawn as recoloured beetles.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum BugKind {
+    Beetle,
+    Spider,
+    Centipede,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BugStats {
+    pub speed: f32,
+    pub vision_range: f32,
+    pub smell_range: f32, // how far ahead it can smell ant trails
+    pub max_health: f32,
+    pub attack: f32,
+    pub defence: f32,
+}
+
+impl BugKind {
+    pub fn stats(&self) -> BugStats {
+        match self {
+            BugKind::Beetle => BugStats {
+                speed: 25.0,
+                vision_range: 30.0,
+                smell_range: 20.0,
+                max_health: 60.0,
+                attack: 4.0,
+                defence: 6.0,
+            },
+            BugKind::Spider => BugStats {
+                speed: 45.0,
+                vision_range: 50.0,
+                smell_range: 10.0,
+                max_health: 30.0,
+                attack: 8.0,
+                defence: 2.0,
+            },
+            BugKind::Centipede => BugStats {
+                speed: 35.0,
+                vision_range: 20.0,
+                smell_range: 40.0,
+                max_health: 45.0,
+                attack: 6.0,
+                defence: 3.0,
+            },
+        }
+    }
+
+    // First frame of the kind's walk cycle in Bugs.png, a block of frames along the row
+    // of beetles facing up, each kind in its own colour
+    pub fn atlas_index(&self) -> usize {
+        let variant = match self {
+            BugKind::Beetle => 0,
+            BugKind::Spider => 3,
+            BugKind::Centipede => 2,
+        };
+        BUG_ATLAS_UP_ROW * BUG_ATLAS_COLUMNS + variant * BUG_ATLAS_VARIANT_FRAMES
+    }
+}
+
+// Layout of Bugs.png
+const BUG_ATLAS_COLUMNS: usize = 12;
+const BUG_ATLAS_VARIANT_FRAMES: usize = 3;
+const BUG_ATLAS_UP_ROW: usize = 3;
+
+/// A hostile insect. Hunts down any ant it can see, follows ant trails by smell
+/// otherwise, and burrows slowly through solid soil, though not rock or water.
+#[derive(Component)]
+pub struct Bug {
+    pub kind: BugKind,
+    pub speed: f32,
+    pub vision_range: f32,
+    pub smell_range: f32,
+    pub heading: Vec2,
+}
+
+impl Bug {
+    pub fn from_kind(kind: BugKind) -> Self {
+        let stats = kind.stats();
+        Self {
+            kind,
+            speed: stats.speed,
+            vision_range: stats.vision_range,
+            smell_range: stats.smell_range,
+            heading: Vec2::X,
+        }
+    }
+}
+
+/// When and how many bugs attack. A wave of `first_wave_size` bugs arrives after
+/// `first_wave_at` seconds, then another every `interval` seconds with `growth` more bugs
+/// than the last, cycling through `kinds`. They come in from the edges of `z_level`, the
+/// surface unless set otherwise.
+#[derive(Resource, Clone, Debug)]
+pub struct BugWaves {
+    pub first_wave_at: f32,
+    pub interval: f32,
+    pub first_wave_size: u32,
+    pub growth: u32,
+    pub kinds: Vec<BugKind>,
+    pub z_level: i32,
+    pub time_until_next: f32,
+    pub waves_spawned: u32,
+}
+
+impl Default for BugWaves {
+    fn default() -> Self {
+        Self::new(60.0, 90.0, 3, 2)
+    }
+}
+
+impl BugWaves {
+    pub fn new(first_wave_at: f32, interval: f32, first_wave_size: u32, growth: u32) -> Self {
+        Self {
+            first_wave_at,
+            interval,
+            first_wave_size,
+            growth,
+            kinds: vec![BugKind::Beetle, BugKind::Spider, BugKind::Centipede],
+            z_level: SURFACE_Z_LEVEL,
+            time_until_next: first_wave_at,
+            waves_spawned: 0,
+        }
+    }
+
+    // No waves at all
+    pub fn none() -> Self {
+        Self {
+            kinds: vec![],
+            ..Self::new(f32::INFINITY, f32::INFINITY, 0, 0)
+        }
+    }
+}
+
+// Speed kept while tunnelling through soil
+const BURROW_SPEED_FACTOR: f32 = 0.3;
+const BUG_SPRITE_Z: f32 = 2.5;
+const BUG_WANDER: f32 = 0.3;
+const BUG_SMELL_SENSORS: usize = 8;
+
+pub fn spawn_bug(
+    commands: &mut Commands,
+    rng: &mut GlobalEntropy<ChaCha8Rng>,
+    position: Vec2,
+    z_level: i32,
+    kind: BugKind,
+) -> Entity {
+    let translation = position.extend(BUG_SPRITE_Z);
+    let stats = kind.stats();
+    commands
+        .spawn((
+            Bug::from_kind(kind),
+            Health::new(stats.max_health),
+            Combat::new(stats.attack, stats.defence),
+            Transform::from_translation(translation),
+            PreviousTranslation(translation),
+            OnZLevel(z_level),
+            rng.fork_rng(),
+        ))
+        .id()
+}
+
+pub fn spawn_bug_waves(
+    mut commands: Commands,
+    mut waves: ResMut<BugWaves>,
+    mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
+    time: Res<Time>,
+) {
+    waves.time_until_next -= time.delta_seconds();
+    if waves.time_until_next > 0.0 || waves.kinds.is_empty() {
+        return;
+    }
+
+    let kind = waves.kinds[waves.waves_spawned as usize % waves.kinds.len()];
+    let count = waves.first_wave_size + waves.growth * waves.waves_spawned;
+    for _ in 0..count {
+        let position = random_map_edge_position(&mut rng);
+        spawn_bug(&mut commands, &mut rng, position, waves.z_level, kind);
+    }
+
+    waves.waves_spawned += 1;
+    waves.time_until_next = waves.interval;
+}
+
+fn random_map_edge_position(rng: &mut GlobalEntropy<ChaCha8Rng>) -> Vec2 {
+    let size = world_map_size();
+    let along = rng.next_u32() as f32 / u32::MAX as f32;
+    match rng.next_u32() % 4 {
+        0 => Vec2::new(along * size.x, 1.0),
+        1 => Vec2::new(along * size.x, size.y - 1.0),
+        2 => Vec2::new(1.0, along * size.y),
+        _ => Vec2::new(size.x - 1.0, along * size.y),
+    }
+}
+
+pub fn update_bug_movement(
+    mut bugs: Query<
+        (
+            &mut Transform,
+            &mut Bug,
+            &OnZLevel,
+            &mut EntropyComponent<ChaCha8Rng>,
+        ),
+        Without<Ant>,
+    >,
+    ants: Query<(&Transform, &OnZLevel), With<Ant>>,
+    levels: Query<(&ZLevel, &PheromoneField)>,
+    time: Res<Time>,
+) {
+    for (mut bug_trans, mut bug, bug_level, mut rng) in bugs.iter_mut() {
+        let position = bug_trans.translation.xy();
+        let level = levels
+            .iter()
+            .find(|(z_level, _)| z_level.z_level == bug_level.0);
+
+        // closest ant in sight
+        let prey = ants
+            .iter()
+            .filter(|(_, ant_level)| **ant_level == *bug_level)
+            .map(|(ant_trans, _)| ant_trans.translation.xy())
+            .filter(|ant_pos| ant_pos.distance(position) <= bug.vision_range)
+            .min_by(|a, b| a.distance(position).total_cmp(&b.distance(position)));
+
+        let mut desired = match prey {
+            Some(ant_pos) => (ant_pos - position).normalize_or_zero(),
+            None => level
+                .and_then(|(_, field)| strongest_trail(field, position, bug.smell_range))
+                .unwrap_or(bug.heading),
+        };
+
+        let wander = Vec2::from_angle(rand_uniform_f32(&mut rng) * TAU) * BUG_WANDER;
+        desired = (desired + wander).normalize_or_zero();
+        if desired == Vec2::ZERO {
+            desired = bug.heading;
+        }
+
+        let in_soil = level.map_or(false, |(z_level, _)| {
+            !z_level.is_tile_walkable(world_pos_to_two_d_index(position))
+        });
+        let speed = if in_soil {
+            bug.speed * BURROW_SPEED_FACTOR
+        } else {
+            bug.speed
+        };
+
+        let size = world_map_size();
+        let mut next =
+            (position + desired * speed * time.delta_seconds()).clamp(Vec2::ZERO, size - Vec2::ONE);
+
+        // turns back from anything it can't get through
+        let blocked = level.map_or(false, |(z_level, _)| {
+            let next_tile = world_pos_to_two_d_index(next);
+            !z_level.is_tile_walkable(next_tile) && !z_level.is_tile_diggable(next_tile)
+        });
+        if blocked {
+            next = position;
+            desired = -desired;
+        }
+
+        bug.heading = desired;
+        bug_trans.translation = next.extend(bug_trans.translation.z);
+        // the sprites face up
+        bug_trans.rotation = Quat::from_rotation_z(desired.y.atan2(desired.x) - FRAC_PI_2);
+    }
+}
+
+// Direction of the strongest ant trail within `range`, if there's any at all
+fn strongest_trail(field: &PheromoneField, position: Vec2, range: f32) -> Option<Vec2> {
+    (0..BUG_SMELL_SENSORS)
+        .map(|i| Vec2::from_angle(i as f32 / BUG_SMELL_SENSORS as f32 * TAU))
+        .map(|dir| {
+            let sensor_pos = position + dir * range;
+            let smell = field.sample_all_colonies(PheromoneKind::HomeThisWay, sensor_pos)
+                + field.sample_all_colonies(PheromoneKind::FoodThisWay, sensor_pos);
+            (dir, smell)
+        })
+        .filter(|(_, smell)| *smell > 0.0)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(dir, _)| dir)
+}
@@ -0,0 +1,348 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use bevy::prelude::*;
+use strum::{EnumCount, IntoEnumIterator};
+use strum_macros::{EnumCount, EnumIter};
+
+use crate::components::*;
+use crate::pathfinding::*;
+use crate::rival::RivalColony;
+use crate::world_map::*;
+
+/// Places ants navigate toward through the flow fields. Each colony has its own, an ant
+/// is only ever led to its own colony's storage and entrances.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, EnumIter, EnumCount)]
+pub enum FlowTarget {
+    FoodStorage,
+    QueenChamber,
+    Chamber,  // anywhere an ant can rest
+    Entrance, // the way between the surface and the nest
+}
+
+impl FlowTarget {
+    // Whether `building`, at `tile` on `level`, is one of the places `colony`'s ants are
+    // after. The rival has no rooms, it keeps its food and rests anywhere in its nest.
+    pub fn is_goal(
+        &self,
+        colony: Colony,
+        rival: &RivalColony,
+        level: i32,
+        tile: UVec2,
+        building: BuildingType,
+    ) -> bool {
+        let rivals_entrance = rival.entrances.contains(&tile);
+        if colony == Colony::PLAYER {
+            match self {
+                FlowTarget::FoodStorage => building == BuildingType::FoodStorage,
+                FlowTarget::QueenChamber => building == BuildingType::QueenChamber,
+                FlowTarget::Chamber => building.is_chamber(),
+                FlowTarget::Entrance => building == BuildingType::Entrance && !rivals_entrance,
+            }
+        } else {
+            match self {
+                FlowTarget::FoodStorage | FlowTarget::Chamber => {
+                    level == rival.z_level
+                        && rival.nest.contains(tile)
+                        && building != BuildingType::None
+                }
+                FlowTarget::QueenChamber => false,
+                FlowTarget::Entrance => building == BuildingType::Entrance && rivals_entrance,
+            }
+        }
+    }
+}
+
+// Every colony's every target, in the order their fields are kept in
+fn colony_targets() -> impl Iterator<Item = (Colony, FlowTarget)> {
+    Colony::all().flat_map(|colony| FlowTarget::iter().map(move |target| (colony, target)))
+}
+
+fn field_index(colony: Colony, target: FlowTarget) -> usize {
+    colony.0 as usize * FlowTarget::COUNT + target as usize
+}
+
+/// Walking distance from every tile to the nearest tile of each colony's `FlowTarget`s, so any
+/// number of ants can find their way without a path each. Lives on the same entity as
+/// the `ZLevel` it belongs to and is brought up to date whenever that level's tiles change.
+#[derive(Component)]
+pub struct FlowFields {
+    distances: Vec<Vec<u32>>, // indexed by `field_index`, then like `ZLevel::tiles`
+    buildings: Vec<BuildingType>, // what the distances were worked out from
+}
+
+impl Default for FlowFields {
+    fn default() -> Self {
+        Self {
+            distances: colony_targets()
+                .map(|_| vec![u32::MAX; MAP_DATA_SIZE])
+                .collect(),
+            // nothing dug and nothing to reach, which is just what the distances say
+            buildings: vec![BuildingType::None; MAP_DATA_SIZE],
+        }
+    }
+}
+
+impl FlowFields {
+    // Walking distance in `STRAIGHT_COST`s per tile, `None` if the target can't be reached
+    pub fn distance(&self, colony: Colony, target: FlowTarget, tile: UVec2) -> Option<u32> {
+        let i = two_d_index_to_one_d_index(tile)?;
+        let distance = self.distances[field_index(colony, target)][i];
+        (distance != u32::MAX).then_some(distance)
+    }
+
+    /// Direction from `world_pos` toward the neighbouring tile that is closest to
+    /// `colony`'s `target`. `None` when already there or when the target can't be reached.
+    pub fn direction(
+        &self,
+        z_level: &ZLevel,
+        colony: Colony,
+        target: FlowTarget,
+        world_pos: Vec2,
+    ) -> Option<Vec2> {
+        if world_pos.x < 0. || world_pos.y < 0. {
+            return None;
+        }
+
+        let tile = world_pos_to_two_d_index(world_pos);
+        let mut best_distance = self.distance(colony, target, tile)?;
+        let mut best_tile = None;
+
+        for (neighbour, _) in walkable_neighbours(z_level, tile) {
+            if let Some(distance) = self.distance(colony, target, neighbour) {
+                if distance < best_distance {
+                    best_distance = distance;
+                    best_tile = Some(neighbour);
+                }
+            }
+        }
+
+        let to_next = (best_tile?.as_vec2() + 0.5) * TILE_SIZE - world_pos;
+        Some(to_next.normalize_or_zero())
+    }
+
+    // Brings every field in line with the tiles that changed since the last update. Tiles
+    // that were dug out or became goals only ever shorten distances, so those are followed
+    // outward from where they happened. A tile filled in or a goal taken away can lengthen
+    // them anywhere it was on the way, so that field is worked out again from scratch.
+    fn update(&mut self, z_level: &ZLevel, rival: &RivalColony) {
+        let changed: Vec<usize> = (0..MAP_DATA_SIZE)
+            .filter(|i| z_level.tiles[*i].building != self.buildings[*i])
+            .collect();
+        if changed.is_empty() {
+            return;
+        }
+
+        for (colony, target) in colony_targets() {
+            let lost_any = changed.iter().any(|i| {
+                let tile = one_d_index_to_two_d_index(*i);
+                let is_goal =
+                    |building| target.is_goal(colony, rival, z_level.z_level, tile, building);
+                let (before, now) = (self.buildings[*i], z_level.tiles[*i].building);
+                (is_goal(before) && !is_goal(now))
+                    || (before != BuildingType::None && now == BuildingType::None)
+            });
+            if lost_any {
+                self.rebuild(z_level, rival, colony, target);
+            } else {
+                self.shorten_around(z_level, rival, colony, target, &changed);
+            }
+        }
+
+        for i in changed {
+            self.buildings[i] = z_level.tiles[i].building;
+        }
+    }
+
+    fn rebuild(
+        &mut self,
+        z_level: &ZLevel,
+        rival: &RivalColony,
+        colony: Colony,
+        target: FlowTarget,
+    ) {
+        let distances = &mut self.distances[field_index(colony, target)];
+        distances.fill(u32::MAX);
+
+        // Dijkstra outward from every goal tile at once
+        let mut open = BinaryHeap::new();
+        for (i, tile) in z_level.tiles.iter().enumerate() {
+            let position = one_d_index_to_two_d_index(i);
+            if target.is_goal(colony, rival, z_level.z_level, position, tile.building) {
+                distances[i] = 0;
+                open.push(Reverse((0, i)));
+            }
+        }
+        spread(z_level, distances, open);
+    }
+
+    // The changed tiles and their neighbours are the only places a shorter way can start
+    // from, a new tile also lets its neighbours step diagonally past it
+    fn shorten_around(
+        &mut self,
+        z_level: &ZLevel,
+        rival: &RivalColony,
+        colony: Colony,
+        target: FlowTarget,
+        changed: &[usize],
+    ) {
+        let distances = &mut self.distances[field_index(colony, target)];
+
+        let mut open = BinaryHeap::new();
+        for i in changed {
+            let tile = one_d_index_to_two_d_index(*i);
+            let around = std::iter::once(tile)
+                .chain(walkable_neighbours(z_level, tile).map(|(neighbour, _)| neighbour));
+            for tile in around {
+                let j = two_d_index_to_one_d_index(tile).unwrap();
+                let building = z_level.tiles[j].building;
+                let distance = if target.is_goal(colony, rival, z_level.z_level, tile, building) {
+                    0
+                } else {
+                    walkable_neighbours(z_level, tile)
+                        .filter_map(|(neighbour, step_cost)| {
+                            let k = two_d_index_to_one_d_index(neighbour).unwrap();
+                            distances[k].checked_add(step_cost)
+                        })
+                        .min()
+                        .unwrap_or(u32::MAX)
+                };
+                if distance < distances[j] {
+                    distances[j] = distance;
+                    open.push(Reverse((distance, j)));
+                }
+            }
+        }
+        spread(z_level, distances, open);
+    }
+}
+
+// Carries the distances in `open` on to every tile they make shorter
+fn spread(z_level: &ZLevel, distances: &mut [u32], mut open: BinaryHeap<Reverse<(u32, usize)>>) {
+    while let Some(Reverse((distance, i))) = open.pop() {
+        if distance > distances[i] {
+            continue;
+        }
+
+        let tile = one_d_index_to_two_d_index(i);
+        for (neighbour, step_cost) in walkable_neighbours(z_level, tile) {
+            let j = two_d_index_to_one_d_index(neighbour).unwrap();
+            if distance + step_cost < distances[j] {
+                distances[j] = distance + step_cost;
+                open.push(Reverse((distances[j], j)));
+            }
+        }
+    }
+}
+
+// The rival only ever gains an entrance along with the tile it's on, so a change to its
+// goals always comes with a change to the level
+pub fn update_flow_fields(
+    mut z_levels: Query<(&ZLevel, &mut FlowFields), Changed<ZLevel>>,
+    rival: Res<RivalColony>,
+) {
+    for (z_level, mut flow_fields) in z_levels.iter_mut() {
+        flow_fields.update(z_level, &rival);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(min: (u32, u32), max: (u32, u32)) -> URect {
+        URect::new(min.0, min.1, max.0, max.1)
+    }
+
+    // Every field worked out from scratch, to hold the updated ones against
+    fn rebuilt(z_level: &ZLevel, rival: &RivalColony) -> FlowFields {
+        let mut flow_fields = FlowFields::default();
+        for (colony, target) in colony_targets() {
+            flow_fields.rebuild(z_level, rival, colony, target);
+        }
+        flow_fields
+    }
+
+    fn assert_up_to_date(flow_fields: &FlowFields, z_level: &ZLevel, rival: &RivalColony) {
+        let expected = rebuilt(z_level, rival);
+        for (colony, target) in colony_targets() {
+            let i = field_index(colony, target);
+            assert!(
+                flow_fields.distances[i] == expected.distances[i],
+                "{colony:?} {target:?} field differs from a full rebuild"
+            );
+        }
+    }
+
+    #[test]
+    fn digging_only_shortens_the_way() {
+        let mut z_level = ZLevel::with_level(0);
+        z_level.set_area(rect((0, 0), (10, 1)), BuildingType::Tunnel);
+        z_level.set_area(rect((9, 0), (10, 10)), BuildingType::Tunnel);
+        z_level.set_area(rect((0, 9), (2, 10)), BuildingType::FoodStorage);
+        let rival = RivalColony::default();
+        let mut flow_fields = FlowFields::default();
+        flow_fields.update(&z_level, &rival);
+        assert_up_to_date(&flow_fields, &z_level, &rival);
+        assert_eq!(
+            flow_fields.distance(Colony::PLAYER, FlowTarget::FoodStorage, UVec2::ZERO),
+            None
+        );
+
+        // a way straight up to the storage, and a second storage across the level
+        z_level.set_area(rect((0, 1), (1, 9)), BuildingType::Tunnel);
+        z_level.set_area(rect((9, 10), (10, 11)), BuildingType::FoodStorage);
+        flow_fields.update(&z_level, &rival);
+        assert_up_to_date(&flow_fields, &z_level, &rival);
+        assert_eq!(
+            flow_fields.distance(Colony::PLAYER, FlowTarget::FoodStorage, UVec2::ZERO),
+            Some(9 * STRAIGHT_COST)
+        );
+    }
+
+    #[test]
+    fn filling_in_and_removing_goals_lengthens_the_way() {
+        let mut z_level = ZLevel::with_level(0);
+        z_level.set_area(rect((0, 0), (10, 10)), BuildingType::Tunnel);
+        z_level.set_area(rect((0, 0), (1, 1)), BuildingType::Nursery);
+        z_level.set_area(rect((9, 9), (10, 10)), BuildingType::QueenChamber);
+        let rival = RivalColony::default();
+        let mut flow_fields = FlowFields::default();
+        flow_fields.update(&z_level, &rival);
+        assert_up_to_date(&flow_fields, &z_level, &rival);
+
+        z_level.set_area(rect((0, 0), (1, 1)), BuildingType::Tunnel);
+        z_level.set_area(rect((5, 0), (6, 9)), BuildingType::None);
+        flow_fields.update(&z_level, &rival);
+        assert_up_to_date(&flow_fields, &z_level, &rival);
+        assert_eq!(
+            flow_fields.distance(Colony::PLAYER, FlowTarget::QueenChamber, UVec2::new(5, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn ants_are_led_to_their_own_colonys_entrance() {
+        let mut z_level = ZLevel::with_level(0);
+        z_level.set_area(rect((0, 0), (10, 1)), BuildingType::Tunnel);
+        z_level.set_area(rect((0, 0), (1, 1)), BuildingType::Entrance);
+        z_level.set_area(rect((9, 0), (10, 1)), BuildingType::Entrance);
+        let rival = RivalColony {
+            entrances: vec![UVec2::new(9, 0)],
+            ..default()
+        };
+        let mut flow_fields = FlowFields::default();
+        flow_fields.update(&z_level, &rival);
+        assert_up_to_date(&flow_fields, &z_level, &rival);
+
+        let tile = UVec2::new(6, 0);
+        assert_eq!(
+            flow_fields.distance(Colony::PLAYER, FlowTarget::Entrance, tile),
+            Some(6 * STRAIGHT_COST)
+        );
+        assert_eq!(
+            flow_fields.distance(Colony::RIVAL, FlowTarget::Entrance, tile),
+            Some(3 * STRAIGHT_COST)
+        );
+    }
+}
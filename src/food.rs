@@ -0,0 +1,92 @@
+use bevy::prelude::*;
+use bevy_prng::ChaCha8Rng;
+use bevy_rand::prelude::*;
+use rand_core::RngCore;
+
+use crate::behavior::*;
+use crate::components::*;
+use crate::jobs::*;
+
+/// Grows a new `Food` source somewhere inside `area` on `z_level` every `interval`
+/// seconds, as long as the area holds fewer than `max_sources`.
+#[derive(Component)]
+pub struct FoodSpawner {
+    pub area: Rect,
+    pub z_level: i32,
+    pub interval: f32,
+    pub time_until_spawn: f32,
+    pub amount: u32, // amount of food in each new source
+    pub max_sources: usize,
+}
+
+impl FoodSpawner {
+    pub fn new(area: Rect, z_level: i32, interval: f32, amount: u32, max_sources: usize) -> Self {
+        Self {
+            area,
+            z_level,
+            interval,
+            time_until_spawn: interval,
+            amount,
+            max_sources,
+        }
+    }
+}
+
+pub fn grow_food(
+    mut commands: Commands,
+    mut spawners: Query<&mut FoodSpawner>,
+    food: Query<(&Transform, &OnZLevel), With<Food>>,
+    mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
+    time: Res<Time>,
+) {
+    for mut spawner in spawners.iter_mut() {
+        spawner.time_until_spawn -= time.delta_seconds();
+        if spawner.time_until_spawn > 0.0 {
+            continue;
+        }
+        spawner.time_until_spawn = spawner.interval;
+
+        let sources_in_area = food
+            .iter()
+            .filter(|(food_trans, food_level)| {
+                food_level.0 == spawner.z_level
+                    && spawner.area.contains(food_trans.translation.xy())
+            })
+            .count();
+        if sources_in_area >= spawner.max_sources {
+            continue;
+        }
+
+        let position = spawner.area.min
+            + Vec2::new(
+                rng.next_u32() as f32 / u32::MAX as f32,
+                rng.next_u32() as f32 / u32::MAX as f32,
+            ) * spawner.area.size();
+        spawn_food(&mut commands, position, spawner.z_level, spawner.amount);
+    }
+}
+
+const HAUL_JOB_PRIORITY: u32 = 1;
+
+pub fn post_haul_jobs(
+    mut board: ResMut<JobBoard>,
+    new_food: Query<(Entity, &Transform, &OnZLevel), Added<Food>>,
+) {
+    for (entity, food_trans, food_level) in new_food.iter() {
+        board.post(
+            JobKind::Haul,
+            HAUL_JOB_PRIORITY,
+            food_level.0,
+            food_trans.translation.xy(),
+            Some(entity),
+        );
+    }
+}
+
+pub fn despawn_depleted_food(mut commands: Commands, food: Query<(Entity, &Food)>) {
+    for (entity, food_source) in food.iter() {
+        if food_source.amount == 0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
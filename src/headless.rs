@@ -0,0 +1,339 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+use bevy::app::AppExit;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+
+use strum::IntoEnumIterator;
+
+use crate::behavior::*;
+use crate::combat::*;
+use crate::components::*;
+use crate::economy::*;
+use crate::enemy::*;
+use crate::jobs::*;
+use crate::labor::*;
+use crate::lifecycle::*;
+use crate::pheromone::*;
+use crate::queen::*;
+use crate::resources::*;
+use crate::rival::*;
+use crate::weather::*;
+use crate::world_map::*;
+
+pub const DEFAULT_HEADLESS_TICKS: u32 = 1000;
+
+/// Runs the simulation for a set number of ticks on `MinimalPlugins`, then prints
+/// a summary and exits.
+pub struct HeadlessPlugin {
+    pub ticks: u32,
+    pub tick_rate_hz: f64,
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        // Without a window there is no frame pacing, so every frame advances the clock
+        // by exactly one simulation tick instead of by however long the frame took
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1.0 / self.tick_rate_hz,
+        )))
+        .insert_resource(HeadlessRun {
+            ticks_total: self.ticks,
+            ticks_run: 0,
+            summary: None,
+        })
+        .add_systems(FixedUpdate, count_ticks)
+        .add_systems(Last, report_when_done);
+    }
+}
+
+#[derive(Resource)]
+pub struct HeadlessRun {
+    pub ticks_total: u32,
+    pub ticks_run: u32,
+    pub summary: Option<String>, // what was printed once the run finished
+}
+
+// Reads `--headless [ticks]` from the command line
+pub fn headless_ticks_from_args() -> Option<u32> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--headless");
+    args.next()?;
+
+    Some(
+        args.next()
+            .and_then(|ticks| ticks.parse().ok())
+            .unwrap_or(DEFAULT_HEADLESS_TICKS),
+    )
+}
+
+// What the colony is up against, bundled to keep `report_when_done` under the system
+// parameter limit
+#[derive(SystemParam)]
+struct Threats<'w, 's> {
+    bugs: Query<'w, 's, &'static Bug>,
+    waves: Res<'w, BugWaves>,
+    combat_log: Res<'w, CombatLog>,
+    rival: Res<'w, RivalColony>,
+    weather: Res<'w, Weather>,
+}
+
+// Counts simulation steps rather than frames, the first frame has no time to step through
+fn count_ticks(mut run: ResMut<HeadlessRun>) {
+    run.ticks_run += 1;
+}
+
+fn report_when_done(
+    mut run: ResMut<HeadlessRun>,
+    mut exit: EventWriter<AppExit>,
+    ants: Query<(&Ant, &OnZLevel, &Colony, &Needs)>,
+    fields: Query<&PheromoneField>,
+    z_levels: Query<&ZLevel>,
+    brood_q: Query<&Brood>,
+    corpses: Query<&Corpse>,
+    queens: Query<&Health, With<Queen>>,
+    food: Res<FoodRes>,
+    food_sources: Query<&Food>,
+    upkeep: Res<FoodUpkeep>,
+    status: Res<ColonyStatus>,
+    board: Res<JobBoard>,
+    labor: Res<LaborStats>,
+    threats: Threats,
+) {
+    // Nothing left to simulate once the colony is lost
+    if run.summary.is_some() || (run.ticks_run < run.ticks_total && *status == ColonyStatus::Alive)
+    {
+        return;
+    }
+
+    let mut summary = String::new();
+    let carrying_food = ants
+        .iter()
+        .filter(|(ant, _, _, _)| matches!(ant.state, AntState::HasFood))
+        .count();
+
+    writeln!(
+        summary,
+        "headless run finished after {} ticks",
+        run.ticks_run
+    )
+    .unwrap();
+    writeln!(
+        summary,
+        "  ants: {} ({} carrying food)",
+        ants.iter().count(),
+        carrying_food
+    )
+    .unwrap();
+    writeln!(
+        summary,
+        "  needs: {} hungry, {} tired, {} resting",
+        ants.iter()
+            .filter(|(_, _, _, needs)| needs.seeking == Some(Need::Food))
+            .count(),
+        ants.iter()
+            .filter(|(_, _, _, needs)| needs.seeking == Some(Need::Rest))
+            .count(),
+        ants.iter()
+            .filter(|(_, _, _, needs)| needs.is_resting)
+            .count(),
+    )
+    .unwrap();
+    writeln!(
+        summary,
+        "  rival colony: {} ants, {} food stored{}",
+        ants.iter()
+            .filter(|(_, _, colony, _)| **colony == Colony::RIVAL)
+            .count(),
+        threats.rival.food.amount(),
+        if threats.rival.upkeep.is_starving {
+            ", starving"
+        } else {
+            ""
+        }
+    )
+    .unwrap();
+    writeln!(
+        summary,
+        "  brood: {} eggs, {} larvae, {} pupae",
+        brood_q
+            .iter()
+            .filter(|brood| brood.stage == BroodStage::Egg)
+            .count(),
+        brood_q
+            .iter()
+            .filter(|brood| brood.stage == BroodStage::Larva)
+            .count(),
+        brood_q
+            .iter()
+            .filter(|brood| brood.stage == BroodStage::Pupa)
+            .count(),
+    )
+    .unwrap();
+    writeln!(
+        summary,
+        "  food sources: {} ({} food left)",
+        food_sources.iter().count(),
+        food_sources
+            .iter()
+            .map(|food_source| food_source.amount)
+            .sum::<u32>()
+    )
+    .unwrap();
+    writeln!(summary, "  corpses: {}", corpses.iter().count()).unwrap();
+    writeln!(
+        summary,
+        "  bugs: {} ({} waves so far)",
+        threats.bugs.iter().count(),
+        threats.waves.waves_spawned
+    )
+    .unwrap();
+    writeln!(
+        summary,
+        "  weather: {:?} for another {:.0}s",
+        threats.weather.kind, threats.weather.time_until_change
+    )
+    .unwrap();
+    writeln!(
+        summary,
+        "  killed in combat: {} ants, {} bugs",
+        threats.combat_log.ants_killed, threats.combat_log.bugs_killed
+    )
+    .unwrap();
+    writeln!(
+        summary,
+        "  colony: {:?}, {} food stored{}",
+        *status,
+        food.amount(),
+        if upkeep.is_starving { ", starving" } else { "" }
+    )
+    .unwrap();
+    for health in queens.iter() {
+        writeln!(
+            summary,
+            "  queen health: {:.1}/{:.1}",
+            health.current, health.max
+        )
+        .unwrap();
+    }
+    for kind in JobKind::iter() {
+        let jobs: Vec<&Job> = board.iter().filter(|job| job.kind == kind).collect();
+        writeln!(
+            summary,
+            "  {:?} jobs: {} ({} reserved)",
+            kind,
+            jobs.len(),
+            jobs.iter().filter(|job| job.reserved_by.is_some()).count()
+        )
+        .unwrap();
+    }
+    writeln!(
+        summary,
+        "  labor: {} idle, {} task switches",
+        labor.idle, labor.switches
+    )
+    .unwrap();
+    for kind in JobKind::iter() {
+        writeln!(
+            summary,
+            "    {:?}: {} ants, stimulus {:.2}",
+            kind,
+            labor.engaged(kind),
+            labor.stimulus(kind)
+        )
+        .unwrap();
+    }
+    for kind in PheromoneKind::iter() {
+        let total: f32 = fields.iter().map(|field| field.total(kind)).sum();
+        writeln!(summary, "  {:?} pheromone: {:.1}", kind, total).unwrap();
+    }
+    writeln!(summary, "  z levels: {}", z_levels.iter().count()).unwrap();
+    for z_level in z_levels.iter() {
+        writeln!(
+            summary,
+            "    {}: {} ants{}",
+            z_level.z_level,
+            ants.iter()
+                .filter(|(_, ant_level, _, _)| ant_level.0 == z_level.z_level)
+                .count(),
+            if z_level.z_level == SURFACE_Z_LEVEL {
+                " (surface)"
+            } else {
+                ""
+            }
+        )
+        .unwrap();
+    }
+
+    print!("{summary}");
+    run.summary = Some(summary);
+    exit.send(AppExit);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_prng::ChaCha8Rng;
+    use bevy_rand::prelude::*;
+
+    use super::*;
+    use crate::{SimulationPlugin, DEFAULT_TICK_RATE_HZ};
+
+    const TICKS: u32 = 120;
+
+    // A few of every caste instead of the default scenario's 2000 ants
+    fn spawn_small_colony(
+        mut commands: Commands,
+        mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
+        rival: Res<RivalColony>,
+    ) {
+        commands.insert_resource(FoodRes::new(50));
+        let nest = (STARTING_QUEEN_CHAMBER_MIN.as_vec2()
+            + STARTING_QUEEN_CHAMBER_SIZE.as_vec2() / 2.0)
+            * TILE_SIZE;
+        spawn_queen(&mut commands, nest, 0);
+        for caste in Caste::iter() {
+            for _ in 0..4 {
+                spawn_ant(&mut commands, &mut rng, nest, 0, caste, Colony::PLAYER);
+            }
+        }
+        spawn_brood(&mut commands, nest, 0);
+        spawn_rival_colony(&mut commands, &mut rng, &rival);
+    }
+
+    fn run_summary(ticks_per_frame: u32) -> String {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugins(HeadlessPlugin {
+                ticks: TICKS,
+                tick_rate_hz: DEFAULT_TICK_RATE_HZ,
+            })
+            .add_plugins(SimulationPlugin {
+                seed: Some(7),
+                spawn_default_scenario: false,
+                ..default()
+            })
+            .add_systems(Startup, spawn_small_colony)
+            // like a slow machine catching up on several ticks every frame
+            .insert_resource(TimeUpdateStrategy::ManualDuration(
+                Duration::from_secs_f64(1.0 / DEFAULT_TICK_RATE_HZ) * ticks_per_frame,
+            ));
+
+        // one more frame than ticks, the first has no time to step through
+        for _ in 0..=TICKS {
+            app.update();
+            if let Some(summary) = &app.world.resource::<HeadlessRun>().summary {
+                return summary.clone();
+            }
+        }
+        panic!("the run never finished");
+    }
+
+    #[test]
+    fn same_seed_and_ticks_give_the_same_colony() {
+        let summary = run_summary(1);
+        assert!(summary.starts_with(&format!("headless run finished after {TICKS} ticks")));
+        assert_eq!(summary, run_summary(1));
+        assert_eq!(summary, run_summary(3));
+    }
+}
@@ -0,0 +1,260 @@
+use bevy::prelude::*;
+use strum_macros::{EnumCount, EnumIter};
+
+use crate::behavior::AntState;
+use crate::components::*;
+use crate::pathfinding::*;
+use crate::world_map::*;
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, EnumIter, EnumCount)]
+pub enum JobKind {
+    Dig,       // work on a designation
+    Haul,      // fetch food back to storage
+    FeedBrood, // tend a hungry larva
+    Guard,     // stand watch somewhere
+}
+
+impl JobKind {
+    // How well suited each caste is to the job, 0 means it never takes it
+    pub fn suitability(&self, caste: Caste) -> f32 {
+        match (self, caste) {
+            (JobKind::Dig, Caste::Digger) => 1.0,
+            (JobKind::Dig, Caste::Worker) => 0.5,
+            (JobKind::Haul, Caste::Forager) => 1.0,
+            (JobKind::Haul, Caste::Worker) => 0.7,
+            (JobKind::FeedBrood, Caste::Nurse) => 1.0,
+            (JobKind::FeedBrood, Caste::Worker) => 0.3,
+            (JobKind::Guard, Caste::Soldier) => 1.0,
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct JobId(u64);
+
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub id: JobId,
+    pub kind: JobKind,
+    pub priority: u32, // higher is claimed first
+    pub z_level: i32,
+    pub position: Vec2,
+    pub target: Option<Entity>, // the job goes away once this is despawned
+    pub reserved_by: Option<Entity>,
+}
+
+/// Every open job in the colony. Anything can post jobs, idle ants that have taken up
+/// the job's task claim them by proximity and caste, and each job is reserved by at most
+/// one ant at a time.
+#[derive(Resource, Default)]
+pub struct JobBoard {
+    jobs: Vec<Job>, // kept in posting order so claiming is deterministic
+    next_id: u64,
+}
+
+impl JobBoard {
+    pub fn post(
+        &mut self,
+        kind: JobKind,
+        priority: u32,
+        z_level: i32,
+        position: Vec2,
+        target: Option<Entity>,
+    ) -> JobId {
+        let id = JobId(self.next_id);
+        self.next_id += 1;
+        self.jobs.push(Job {
+            id,
+            kind,
+            priority,
+            z_level,
+            position,
+            target,
+            reserved_by: None,
+        });
+        id
+    }
+
+    pub fn get(&self, id: JobId) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    pub fn remove(&mut self, id: JobId) {
+        self.jobs.retain(|job| job.id != id);
+    }
+
+    pub fn remove_for_target(&mut self, target: Entity, kind: JobKind) {
+        self.jobs
+            .retain(|job| job.target != Some(target) || job.kind != kind);
+    }
+
+    pub fn has_job_for(&self, target: Entity, kind: JobKind) -> bool {
+        self.jobs
+            .iter()
+            .any(|job| job.target == Some(target) && job.kind == kind)
+    }
+
+    pub fn reserve(&mut self, id: JobId, ant: Entity) -> bool {
+        match self.jobs.iter_mut().find(|job| job.id == id) {
+            Some(job) if job.reserved_by.is_none() => {
+                job.reserved_by = Some(ant);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn release(&mut self, id: JobId) {
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+            job.reserved_by = None;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Job> {
+        self.jobs.iter()
+    }
+}
+
+/// The job an ant has claimed, with the tiles it still has to walk to get there.
+#[derive(Component)]
+pub struct AssignedJob {
+    pub id: JobId,
+    pub kind: JobKind,
+    path: Vec<UVec2>,
+    next: usize,
+}
+
+// Waypoints this close count as reached
+const WAYPOINT_RADIUS: f32 = 6.0;
+
+impl AssignedJob {
+    // Center of the next tile on the way to the job, `None` once there
+    pub fn next_waypoint(&mut self, world_pos: Vec2) -> Option<Vec2> {
+        while let Some(tile) = self.path.get(self.next) {
+            let waypoint = (tile.as_vec2() + 0.5) * TILE_SIZE;
+            if (waypoint - world_pos).length() > WAYPOINT_RADIUS {
+                return Some(waypoint);
+            }
+            self.next += 1;
+        }
+        None
+    }
+}
+
+// Idle ants further away than this don't notice a job
+const JOB_CLAIM_RADIUS: f32 = 300.0;
+
+pub fn claim_jobs(
+    mut commands: Commands,
+    mut board: ResMut<JobBoard>,
+    mut paths: ResMut<PathCache>,
+    ants: Query<(Entity, &Transform, &OnZLevel, &Ant), Without<AssignedJob>>,
+    z_levels: Query<&ZLevel>,
+) {
+    let mut idle_ants: Vec<(Entity, Vec2, i32, Option<JobKind>, Caste)> = ants
+        .iter()
+        .filter(|(_, _, _, ant)| ant.state == AntState::Wandering)
+        .map(|(entity, trans, ant_level, ant)| {
+            (
+                entity,
+                trans.translation.xy(),
+                ant_level.0,
+                ant.task,
+                ant.caste,
+            )
+        })
+        .collect();
+
+    let mut open_jobs: Vec<Job> = board
+        .iter()
+        .filter(|job| job.reserved_by.is_none())
+        .cloned()
+        .collect();
+    // stable, so equal priorities stay in posting order
+    open_jobs.sort_by(|a, b| b.priority.cmp(&a.priority));
+
+    for job in open_jobs {
+        let Some(z_level) = z_levels
+            .iter()
+            .find(|z_level| z_level.z_level == job.z_level)
+        else {
+            continue;
+        };
+
+        let best = idle_ants
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, _, ant_level, task, _))| {
+                *ant_level == job.z_level && *task == Some(job.kind)
+            })
+            .map(|(i, (_, ant_pos, _, _, caste))| {
+                let distance = (*ant_pos - job.position).length();
+                (
+                    i,
+                    distance,
+                    job.kind.suitability(*caste) / (1.0 + distance / TILE_SIZE.x),
+                )
+            })
+            .filter(|(_, distance, _)| *distance <= JOB_CLAIM_RADIUS)
+            .max_by(|(_, _, a), (_, _, b)| a.total_cmp(b));
+        let Some((i, _, _)) = best else {
+            continue;
+        };
+
+        let (ant, ant_pos, _, _, _) = idle_ants[i];
+        let Some(path) = paths.find_path(
+            z_level,
+            world_pos_to_two_d_index(ant_pos),
+            world_pos_to_two_d_index(job.position),
+        ) else {
+            continue;
+        };
+
+        if board.reserve(job.id, ant) {
+            commands.entity(ant).insert(AssignedJob {
+                id: job.id,
+                kind: job.kind,
+                path: path.to_vec(),
+                next: 0,
+            });
+            idle_ants.swap_remove(i);
+        }
+    }
+}
+
+// Drops jobs whose target is gone, releases reservations held by ants that are gone
+// and takes finished or vanished jobs off the ants
+pub fn release_stale_jobs(
+    mut commands: Commands,
+    mut board: ResMut<JobBoard>,
+    ants: Query<(Entity, &Ant, &AssignedJob)>,
+    entities: Query<()>,
+) {
+    board.jobs.retain(|job| {
+        job.target
+            .map_or(true, |target| entities.get(target).is_ok())
+    });
+
+    for job in board.jobs.iter_mut() {
+        if let Some(ant) = job.reserved_by {
+            if ants
+                .get(ant)
+                .map_or(true, |(_, _, assigned)| assigned.id != job.id)
+            {
+                job.reserved_by = None;
+            }
+        }
+    }
+
+    for (entity, ant, assigned) in ants.iter() {
+        // a hauler's job is done once it has picked up food, leaving it for the next ant
+        let is_done = assigned.kind == JobKind::Haul && ant.state == AntState::HasFood;
+        if is_done {
+            board.release(assigned.id);
+        }
+        if is_done || board.get(assigned.id).is_none() {
+            commands.entity(entity).remove::<AssignedJob>();
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use bevy::prelude::*;
+use bevy_prng::ChaCha8Rng;
+use bevy_rand::prelude::*;
+use rand_core::RngCore;
+use strum::{EnumCount, IntoEnumIterator};
+
+use crate::behavior::AntState;
+use crate::components::*;
+use crate::jobs::*;
+use crate::resources::FoodRes;
+
+/// How much stimulus each task needs before the ant takes it up, lower thresholds
+/// respond sooner. `f32::INFINITY` means never.
+#[derive(Clone, Copy, Debug)]
+pub struct TaskThresholds([f32; JobKind::COUNT]); // indexed by `JobKind as usize`
+
+// Threshold of an ant perfectly suited to a task
+const BASE_THRESHOLD: f32 = 5.0;
+// Each ant's thresholds are spread up to this fraction either way of its caste's
+const THRESHOLD_SPREAD: f32 = 0.5;
+
+impl TaskThresholds {
+    pub fn for_caste(caste: Caste) -> Self {
+        let mut thresholds = [f32::INFINITY; JobKind::COUNT];
+        for kind in JobKind::iter() {
+            let suitability = kind.suitability(caste);
+            if suitability > 0.0 {
+                thresholds[kind as usize] = BASE_THRESHOLD / suitability;
+            }
+        }
+        Self(thresholds)
+    }
+
+    pub fn randomized(caste: Caste, rng: &mut GlobalEntropy<ChaCha8Rng>) -> Self {
+        let mut thresholds = Self::for_caste(caste);
+        for threshold in thresholds.0.iter_mut() {
+            let spread = rng.next_u32() as f32 / u32::MAX as f32 * 2.0 - 1.0;
+            *threshold *= 1.0 + spread * THRESHOLD_SPREAD;
+        }
+        thresholds
+    }
+
+    pub fn get(&self, kind: JobKind) -> f32 {
+        self.0[kind as usize]
+    }
+
+    pub fn set(&mut self, kind: JobKind, threshold: f32) {
+        self.0[kind as usize] = threshold;
+    }
+
+    // Chance of taking up the task at `stimulus`, the classic s² / (s² + θ²)
+    pub fn response(&self, kind: JobKind, stimulus: f32) -> f32 {
+        let threshold = self.get(kind);
+        if !threshold.is_finite() {
+            return 0.0;
+        }
+        let stimulus_sq = stimulus * stimulus;
+        stimulus_sq / (stimulus_sq + threshold * threshold).max(f32::EPSILON)
+    }
+}
+
+/// Colony wide stimulus for each task. Grows with unclaimed jobs of that kind (and for
+/// hauling, with an empty larder) and shrinks with every ant already doing it.
+#[derive(Resource, Default)]
+pub struct TaskStimuli([f32; JobKind::COUNT]); // indexed by `JobKind as usize`
+
+impl TaskStimuli {
+    pub fn get(&self, kind: JobKind) -> f32 {
+        self.0[kind as usize]
+    }
+}
+
+/// How the colony's ants are currently split between tasks, for studying how the
+/// division of labor emerges.
+#[derive(Resource, Default, Debug)]
+pub struct LaborStats {
+    pub engaged: [usize; JobKind::COUNT], // indexed by `JobKind as usize`
+    pub idle: usize,
+    pub stimuli: [f32; JobKind::COUNT],
+    pub switches: u64, // ants taking up or dropping a task since the start
+}
+
+impl LaborStats {
+    pub fn engaged(&self, kind: JobKind) -> usize {
+        self.engaged[kind as usize]
+    }
+
+    pub fn stimulus(&self, kind: JobKind) -> f32 {
+        self.stimuli[kind as usize]
+    }
+}
+
+// Stimulus added per second for each unclaimed job
+const STIMULUS_PER_JOB: f32 = 0.5;
+// ...and taken away per second for each ant on the task
+const STIMULUS_PER_WORKER: f32 = 0.05;
+// Hauling stimulus added per second when the stock is empty, falling off as it fills
+const HAUL_STIMULUS_WHEN_EMPTY: f32 = 2.0;
+const FOOD_COMFORT_LEVEL: f32 = 200.0;
+const MAX_STIMULUS: f32 = 50.0;
+// Chance per second an idle ant weighs up the tasks, and an engaged one without a job
+// gives up its task
+const ENGAGE_RATE: f32 = 0.5;
+const QUIT_RATE: f32 = 0.05;
+
+pub fn update_task_stimuli(
+    mut stimuli: ResMut<TaskStimuli>,
+    mut stats: ResMut<LaborStats>,
+    board: Res<JobBoard>,
+    food: Res<FoodRes>,
+    ants: Query<(&Ant, &Colony)>,
+    time: Res<Time>,
+) {
+    let mut engaged = [0; JobKind::COUNT];
+    let mut idle = 0;
+    // only the player's colony works off the job board
+    for (ant, _) in ants.iter().filter(|(_, colony)| **colony == Colony::PLAYER) {
+        match ant.task {
+            Some(kind) => engaged[kind as usize] += 1,
+            None => idle += 1,
+        }
+    }
+
+    for kind in JobKind::iter() {
+        let open_jobs = board
+            .iter()
+            .filter(|job| job.kind == kind && job.reserved_by.is_none())
+            .count();
+        let mut demand = open_jobs as f32 * STIMULUS_PER_JOB;
+        if kind == JobKind::Haul {
+            demand += HAUL_STIMULUS_WHEN_EMPTY
+                * (1.0 - food.amount() as f32 / FOOD_COMFORT_LEVEL).max(0.0);
+        }
+
+        let stimulus = &mut stimuli.0[kind as usize];
+        *stimulus +=
+            (demand - engaged[kind as usize] as f32 * STIMULUS_PER_WORKER) * time.delta_seconds();
+        *stimulus = stimulus.clamp(0.0, MAX_STIMULUS);
+    }
+
+    stats.engaged = engaged;
+    stats.idle = idle;
+    stats.stimuli = stimuli.0;
+}
+
+pub fn allocate_tasks(
+    mut ants: Query<(
+        &mut Ant,
+        &Colony,
+        &mut EntropyComponent<ChaCha8Rng>,
+        Option<&AssignedJob>,
+    )>,
+    stimuli: Res<TaskStimuli>,
+    mut stats: ResMut<LaborStats>,
+    time: Res<Time>,
+) {
+    let tasks: Vec<JobKind> = JobKind::iter().collect();
+
+    for (mut ant, colony, mut rng, assigned_job) in ants.iter_mut() {
+        if *colony != Colony::PLAYER {
+            continue;
+        }
+        let roll = rng.next_u32() as f32 / u32::MAX as f32;
+
+        match ant.task {
+            Some(_) => {
+                // an ant sees its job through before giving up the task
+                if assigned_job.is_none() && roll < QUIT_RATE * time.delta_seconds() {
+                    ant.task = None;
+                    stats.switches += 1;
+                }
+            }
+            None => {
+                if ant.state != AntState::Wandering || roll >= ENGAGE_RATE * time.delta_seconds() {
+                    continue;
+                }
+
+                // start from a random task so none of them gets first pick
+                let first = rng.next_u32() as usize % JobKind::COUNT;
+                for i in 0..JobKind::COUNT {
+                    let kind = tasks[(first + i) % JobKind::COUNT];
+                    let chance = ant.thresholds.response(kind, stimuli.get(kind));
+                    if (rng.next_u32() as f32 / u32::MAX as f32) < chance {
+                        ant.task = Some(kind);
+                        stats.switches += 1;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use std::f32::consts::TAU;
+
+use bevy::input::mouse::MouseWheel;
+use bevy::transform::TransformSystem;
+use bevy::{
+    prelude::*,
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+};
+
+use bevy_prng::ChaCha8Rng;
+use bevy_rand::prelude::*;
+use rand_core::RngCore;
+
+pub mod behavior;
+pub mod camera;
+pub mod combat;
+pub mod components;
+pub mod crowding;
+pub mod designation;
+pub mod economy;
+pub mod enemy;
+pub mod flow_field;
+pub mod food;
+pub mod headless;
+pub mod jobs;
+pub mod labor;
+pub mod lifecycle;
+pub mod needs;
+pub mod nest;
+pub mod pathfinding;
+pub mod pheromone;
+pub mod queen;
+pub mod resources;
+pub mod rival;
+pub mod sprite;
+pub mod util;
+pub mod weather;
+pub mod world_map;
+pub mod world_ui;
+pub mod worldgen;
+
+use components::*;
+use resources::{ColonyStatus, FoodRes};
+use util::*;
+
+const BACKGROUND_COLOR: Color = Color::rgb(0.1, 0.1, 0.1);
+const PLAYER_COLOR: Color = Color::rgb(1.0, 0.5, 0.5);
+
+const ANT_SIZE: Vec3 = Vec3::new(30.0, 30.0, 0.0);
+
+pub const DEFAULT_TICK_RATE_HZ: f64 = 60.0;
+
+/// The colony model: ants, pheromones, food and the `ZLevel` map data. Runs on
+/// `MinimalPlugins` and never touches a window, the renderer or the tilemap.
+///
+/// The simulation only ever advances in whole ticks of `1 / tick_rate_hz` seconds, so a
+/// seed plus a tick count always produces the same colony regardless of frame rate.
+pub struct SimulationPlugin {
+    pub tick_rate_hz: f64,
+    pub seed: Option<u64>,
+    // The starting colony with its queen and brood, the food spawner and the rival colony.
+    // Without it the generated world is left empty for tools and tests to fill themselves.
+    pub spawn_default_scenario: bool,
+}
+
+impl Default for SimulationPlugin {
+    fn default() -> Self {
+        Self {
+            tick_rate_hz: DEFAULT_TICK_RATE_HZ,
+            seed: None,
+            spawn_default_scenario: true,
+        }
+    }
+}
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        let entropy_plugin = match self.seed {
+            Some(seed) => {
+                let mut seed_bytes = [0; 32];
+                seed_bytes[..8].copy_from_slice(&seed.to_le_bytes());
+                EntropyPlugin::<ChaCha8Rng>::with_seed(seed_bytes)
+            }
+            None => EntropyPlugin::<ChaCha8Rng>::default(),
+        };
+
+        app.add_plugins(entropy_plugin)
+            .add_plugins(world_map::ZLevelPlugin)
+            .insert_resource(Time::<Fixed>::from_hz(self.tick_rate_hz))
+            .init_resource::<pheromone::PheromoneSettings>()
+            .init_resource::<pheromone::PheromoneResponses>()
+            .add_event::<pheromone::AlarmRaised>()
+            .init_resource::<FoodRes>()
+            .init_resource::<ColonyStatus>()
+            .add_event::<queen::ColonyLost>()
+            .init_resource::<economy::FoodUpkeep>()
+            .add_event::<economy::FoodDelivered>()
+            .init_resource::<nest::NestTargets>()
+            .init_resource::<pathfinding::PathCache>()
+            .init_resource::<designation::Designations>()
+            .init_resource::<jobs::JobBoard>()
+            .init_resource::<labor::TaskStimuli>()
+            .init_resource::<labor::LaborStats>()
+            .init_resource::<crowding::SpatialHash>()
+            .init_resource::<enemy::BugWaves>()
+            .init_resource::<combat::CombatLog>()
+            .init_resource::<rival::RivalColony>()
+            .init_resource::<weather::Weather>()
+            .add_event::<designation::Designate>()
+            .add_event::<designation::CancelDesignation>()
+            // player input arrives outside the fixed timestep
+            .add_systems(PreUpdate, designation::apply_designation_requests)
+            .add_systems(
+                FixedUpdate,
+                // chained so the order the ants consume their rngs never changes between runs
+                (
+                    (
+                        pathfinding::invalidate_paths,
+                        jobs::release_stale_jobs,
+                        food::post_haul_jobs,
+                        queen::post_guard_jobs,
+                        labor::update_task_stimuli,
+                        labor::allocate_tasks,
+                        jobs::claim_jobs,
+                    )
+                        .chain(),
+                    (
+                        behavior::record_previous_translation,
+                        nest::update_nest_targets,
+                        flow_field::update_flow_fields,
+                        crowding::rebuild_spatial_hash,
+                        behavior::update_ant_movement,
+                        enemy::spawn_bug_waves,
+                        enemy::update_bug_movement,
+                        combat::resolve_melee,
+                        combat::resolve_skirmishes,
+                        combat::remove_dead,
+                        food::despawn_depleted_food,
+                        food::grow_food,
+                        economy::collect_deliveries,
+                        designation::work_designations,
+                        behavior::spawn_pheromones,
+                        behavior::emit_pheromones,
+                        behavior::decay_pheromones,
+                    )
+                        .chain(),
+                    (
+                        lifecycle::feed_brood,
+                        lifecycle::advance_brood,
+                        lifecycle::age_ants,
+                        needs::update_needs,
+                        lifecycle::decay_corpses,
+                        economy::colony_upkeep,
+                        rival::rival_strategy,
+                        rival::rival_dig_tunnels,
+                        weather::update_weather,
+                        queen::queen_upkeep,
+                        queen::queen_lay_eggs,
+                        queen::check_queen_alive,
+                    )
+                        .chain(),
+                )
+                    .chain(),
+            );
+
+        if self.spawn_default_scenario {
+            app.add_systems(Startup, setup);
+        }
+    }
+}
+
+/// Camera, tilemap, UI, sprites and debug drawing on top of a `SimulationPlugin`.
+/// Needs `DefaultPlugins`.
+pub struct PresentationPlugin;
+
+impl Plugin for PresentationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(camera::CameraPlugin)
+            .add_plugins(sprite::AnimationTestPlugin)
+            .add_plugins(sprite::AntSpritePlugin)
+            .add_plugins(sprite::BugSpritePlugin)
+            .add_plugins(world_map::WorldMapPlugin)
+            .add_plugins(world_ui::WorldUIPlugin)
+            .insert_resource(ClearColor(BACKGROUND_COLOR))
+            .add_systems(Startup, setup_player)
+            .add_systems(Update, (bevy::window::close_on_esc, add_food_meshes))
+            .add_systems(
+                Update,
+                (
+                    // behavior::debug_ants,
+                    behavior::debug_phers,
+                    behavior::debug_ants_minimal,
+                    lifecycle::debug_lifecycle,
+                    queen::debug_queen,
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                behavior::interpolate_rendered_transforms
+                    .after(TransformSystem::TransformPropagate),
+            )
+            .add_event::<MouseWheel>();
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
+    rival: Res<rival::RivalColony>,
+) {
+    commands.insert_resource(FoodRes::new(STARTING_FOOD));
+
+    let queen_chamber_center = (world_map::STARTING_QUEEN_CHAMBER_MIN.as_vec2()
+        + world_map::STARTING_QUEEN_CHAMBER_SIZE.as_vec2() / 2.0)
+        * world_map::TILE_SIZE;
+    queen::spawn_queen(&mut commands, queen_chamber_center, 0);
+
+    let food_patch_min = world_map::world_map_center() + Vec2::new(100.0, 100.0);
+    commands.spawn(food::FoodSpawner::new(
+        Rect::from_corners(food_patch_min, food_patch_min + Vec2::splat(200.0)),
+        world_map::SURFACE_Z_LEVEL,
+        FOOD_REGROWTH_INTERVAL,
+        STARTING_FOOD_SOURCE_AMOUNT,
+        3,
+    ));
+
+    for _ in 0..2000 {
+        let caste = lifecycle::random_caste(&mut rng);
+        // Scattered around the nest rather than stacked on a single point
+        let offset = Vec2::from_angle(rng.next_u32() as f32 / u32::MAX as f32 * TAU)
+            * (rng.next_u32() as f32 / u32::MAX as f32).sqrt()
+            * STARTING_SPREAD_RADIUS;
+        let ant = behavior::spawn_ant(
+            &mut commands,
+            &mut rng,
+            world_map::world_map_center() + offset,
+            0,
+            caste,
+            Colony::PLAYER,
+        );
+
+        // Spread the starting ants' ages so they don't all die of old age at once, and
+        // their needs so they don't all go off to eat at once
+        let lifespan = caste.stats().lifespan;
+        commands.entity(ant).insert((
+            Age {
+                age: rng.next_u32() as f32 / u32::MAX as f32 * lifespan * STARTING_MAX_AGE,
+                lifespan,
+            },
+            Needs {
+                satiety: 1.0 - rng.next_u32() as f32 / u32::MAX as f32 * STARTING_MAX_HUNGER,
+                energy: 1.0 - rng.next_u32() as f32 / u32::MAX as f32 * STARTING_MAX_FATIGUE,
+                ..default()
+            },
+        ));
+    }
+
+    for i in 0..STARTING_EGGS {
+        let tile = world_map::STARTING_NURSERY_MIN
+            + UVec2::new(
+                i % world_map::STARTING_NURSERY_SIZE.x,
+                (i / world_map::STARTING_NURSERY_SIZE.x) % world_map::STARTING_NURSERY_SIZE.y,
+            );
+        lifecycle::spawn_brood(
+            &mut commands,
+            (tile.as_vec2() + 0.5) * world_map::TILE_SIZE,
+            0,
+        );
+    }
+
+    rival::spawn_rival_colony(&mut commands, &mut rng, &rival);
+}
+
+// Starting ants are up to this fraction of the way through their lifespan
+const STARTING_MAX_AGE: f32 = 0.5;
+// ...and have used up to these fractions of their satiety and energy
+const STARTING_MAX_HUNGER: f32 = 0.6;
+const STARTING_MAX_FATIGUE: f32 = 0.6;
+const STARTING_EGGS: u32 = 20;
+// Starting ants are spread evenly over a disc this big around the map center
+const STARTING_SPREAD_RADIUS: f32 = 64.0;
+const STARTING_FOOD: u64 = 200;
+const STARTING_FOOD_SOURCE_AMOUNT: u32 = 100;
+const FOOD_REGROWTH_INTERVAL: f32 = 20.0;
+
+fn setup_player(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(shape::Circle::default().into()).into(),
+            material: materials.add(ColorMaterial::from(PLAYER_COLOR)),
+            transform: Transform::from_translation(world_map::world_map_center_3d())
+                .with_scale(ANT_SIZE),
+            ..default()
+        },
+        Player,
+    ));
+}
+
+// Food is spawned by the simulation with only a transform, the mesh is added here so
+// headless runs never touch the renderer's assets
+fn add_food_meshes(
+    mut commands: Commands,
+    food_q: Query<Entity, Added<Food>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for entity in food_q.iter() {
+        commands.entity(entity).insert((
+            Mesh2dHandle(meshes.add(shape::Circle::default().into())),
+            materials.add(ColorMaterial::from(Color::ORANGE_RED)),
+            GlobalTransform::default(),
+            VisibilityBundle::default(),
+        ));
+    }
+}
@@ -0,0 +1,271 @@
+use bevy::math::*;
+use bevy::prelude::*;
+use bevy_prng::ChaCha8Rng;
+use bevy_rand::prelude::*;
+use rand_core::RngCore;
+
+use crate::behavior::*;
+use crate::components::*;
+use crate::economy::*;
+use crate::jobs::*;
+use crate::rival::RivalColony;
+use crate::world_map::SelectedZLevel;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum BroodStage {
+    Egg,
+    Larva,
+    Pupa,
+}
+
+impl BroodStage {
+    // Seconds spent in the stage before moving on. Larvae only grow while fed.
+    pub fn duration(&self) -> f32 {
+        match self {
+            BroodStage::Egg => 10.0,
+            BroodStage::Larva => 20.0,
+            BroodStage::Pupa => 10.0,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Brood {
+    pub stage: BroodStage,
+    pub stage_time: f32,
+    pub time_since_fed: f32,
+}
+
+#[derive(Component)]
+pub struct Corpse {
+    pub time_left: f32,
+}
+
+// Nurses (or ants that took up feeding brood) within this distance keep a larva fed
+const NURSE_FEED_RADIUS: f32 = 16.0;
+// A larva that goes unfed for this long stops growing
+const LARVA_HUNGRY_TIME: f32 = 5.0;
+// ...and dies if it goes unfed for this long
+const LARVA_STARVE_TIME: f32 = 30.0;
+// Larvae left unfed this long ask for a nurse
+const LARVA_FEED_JOB_TIME: f32 = 2.0;
+const FEED_BROOD_JOB_PRIORITY: u32 = 3;
+const CORPSE_DECAY_TIME: f32 = 60.0;
+
+// Relative share of each caste among newly hatched (and starting) ants
+const CASTE_WEIGHTS: [(Caste, u32); 5] = [
+    (Caste::Worker, 40),
+    (Caste::Forager, 25),
+    (Caste::Soldier, 10),
+    (Caste::Nurse, 10),
+    (Caste::Digger, 15),
+];
+
+pub fn random_caste(rng: &mut GlobalEntropy<ChaCha8Rng>) -> Caste {
+    let total: u32 = CASTE_WEIGHTS.iter().map(|(_, weight)| weight).sum();
+    let mut roll = rng.next_u32() % total;
+
+    for (caste, weight) in CASTE_WEIGHTS {
+        if roll < weight {
+            return caste;
+        }
+        roll -= weight;
+    }
+
+    Caste::Worker
+}
+
+pub fn spawn_brood(commands: &mut Commands, position: Vec2, z_level: i32) -> Entity {
+    commands
+        .spawn((
+            Transform::from_translation(Vec3::new(position.x, position.y, 0.5)),
+            OnZLevel(z_level),
+            Brood {
+                stage: BroodStage::Egg,
+                stage_time: 0.0,
+                time_since_fed: 0.0,
+            },
+        ))
+        .id()
+}
+
+pub fn spawn_corpse(commands: &mut Commands, position: Vec2, z_level: i32) -> Entity {
+    commands
+        .spawn((
+            Transform::from_translation(Vec3::new(position.x, position.y, 0.5)),
+            OnZLevel(z_level),
+            Corpse {
+                time_left: CORPSE_DECAY_TIME,
+            },
+        ))
+        .id()
+}
+
+pub fn feed_brood(
+    mut brood_q: Query<(Entity, &Transform, &OnZLevel, &mut Brood)>,
+    ants: Query<(&Transform, &OnZLevel, &Colony, &Ant), Without<Brood>>,
+    mut upkeep: ResMut<FoodUpkeep>,
+    mut board: ResMut<JobBoard>,
+    time: Res<Time>,
+) {
+    let nurse_positions: Vec<(Vec2, OnZLevel)> = ants
+        .iter()
+        .filter(|(_, _, colony, ant)| {
+            **colony == Colony::PLAYER
+                && (ant.caste == Caste::Nurse || ant.task == Some(JobKind::FeedBrood))
+        })
+        .map(|(trans, nurse_level, _, _)| (trans.translation.xy(), *nurse_level))
+        .collect();
+
+    for (entity, brood_trans, brood_level, mut brood) in brood_q.iter_mut() {
+        if brood.stage != BroodStage::Larva {
+            continue;
+        }
+
+        let brood_pos = brood_trans.translation.xy();
+        let is_tended = nurse_positions.iter().any(|(nurse_pos, nurse_level)| {
+            nurse_level == brood_level && (*nurse_pos - brood_pos).length() <= NURSE_FEED_RADIUS
+        });
+
+        // Nurses have nothing to feed them with while the colony is starving
+        if is_tended && !upkeep.is_starving {
+            upkeep.charge(LARVA_FOOD_PER_SECOND * time.delta_seconds());
+            brood.time_since_fed = 0.0;
+            board.remove_for_target(entity, JobKind::FeedBrood);
+        } else {
+            brood.time_since_fed += time.delta_seconds();
+        }
+
+        if brood.time_since_fed >= LARVA_FEED_JOB_TIME
+            && !board.has_job_for(entity, JobKind::FeedBrood)
+        {
+            board.post(
+                JobKind::FeedBrood,
+                FEED_BROOD_JOB_PRIORITY,
+                brood_level.0,
+                brood_pos,
+                Some(entity),
+            );
+        }
+    }
+}
+
+pub fn advance_brood(
+    mut commands: Commands,
+    mut brood_q: Query<(Entity, &Transform, &OnZLevel, &mut Brood)>,
+    mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
+    time: Res<Time>,
+) {
+    for (entity, brood_trans, brood_level, mut brood) in brood_q.iter_mut() {
+        let position = brood_trans.translation.xy();
+
+        if brood.stage == BroodStage::Larva {
+            if brood.time_since_fed >= LARVA_STARVE_TIME {
+                commands.entity(entity).despawn();
+                spawn_corpse(&mut commands, position, brood_level.0);
+                continue;
+            }
+            if brood.time_since_fed >= LARVA_HUNGRY_TIME {
+                continue;
+            }
+        }
+
+        brood.stage_time += time.delta_seconds();
+        if brood.stage_time < brood.stage.duration() {
+            continue;
+        }
+
+        brood.stage_time = 0.0;
+        match brood.stage {
+            BroodStage::Egg => brood.stage = BroodStage::Larva,
+            BroodStage::Larva => brood.stage = BroodStage::Pupa,
+            BroodStage::Pupa => {
+                commands.entity(entity).despawn();
+                let caste = random_caste(&mut rng);
+                spawn_ant(
+                    &mut commands,
+                    &mut rng,
+                    position,
+                    brood_level.0,
+                    caste,
+                    Colony::PLAYER,
+                );
+            }
+        }
+    }
+}
+
+pub fn age_ants(
+    mut commands: Commands,
+    mut ants: Query<(Entity, &Transform, &OnZLevel, &Colony, &mut Age), With<Ant>>,
+    upkeep: Res<FoodUpkeep>,
+    rival: Res<RivalColony>,
+    time: Res<Time>,
+) {
+    for (entity, ant_trans, ant_level, colony, mut age) in ants.iter_mut() {
+        let is_starving = if *colony == Colony::PLAYER {
+            upkeep.is_starving
+        } else {
+            rival.upkeep.is_starving
+        };
+        let ageing_rate = if is_starving {
+            STARVATION_AGEING_FACTOR
+        } else {
+            1.0
+        };
+        age.age += time.delta_seconds() * ageing_rate;
+
+        if age.age >= age.lifespan {
+            commands.entity(entity).despawn();
+            spawn_corpse(&mut commands, ant_trans.translation.xy(), ant_level.0);
+        }
+    }
+}
+
+pub fn decay_corpses(
+    mut commands: Commands,
+    mut corpses: Query<(Entity, &mut Corpse)>,
+    time: Res<Time>,
+) {
+    for (entity, mut corpse) in corpses.iter_mut() {
+        corpse.time_left -= time.delta_seconds();
+
+        if corpse.time_left <= 0.0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+pub fn debug_lifecycle(
+    brood_q: Query<(&Transform, &OnZLevel, &Brood)>,
+    corpses: Query<(&Transform, &OnZLevel), With<Corpse>>,
+    selected_z_level_q: Query<&SelectedZLevel>,
+    mut gizmos: Gizmos,
+) {
+    let selected_z_level = selected_z_level_q.single();
+
+    for (brood_trans, brood_level, brood) in brood_q.iter() {
+        if brood_level.0 != selected_z_level.0 {
+            continue;
+        }
+
+        let (radius, color) = match brood.stage {
+            BroodStage::Egg => (1.5, Color::WHITE),
+            BroodStage::Larva => (2.5, Color::BEIGE),
+            BroodStage::Pupa => (3.0, Color::GOLD),
+        };
+        gizmos
+            .circle_2d(brood_trans.translation.xy(), radius, color)
+            .segments(8);
+    }
+
+    for (corpse_trans, corpse_level) in corpses.iter() {
+        if corpse_level.0 != selected_z_level.0 {
+            continue;
+        }
+
+        gizmos
+            .circle_2d(corpse_trans.translation.xy(), 2.0, Color::GRAY)
+            .segments(6);
+    }
+}
@@ -1,108 +1,35 @@
-use bevy::input::mouse::MouseWheel;
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use bevy::prelude::*;
 
-use bevy_prng::ChaCha8Rng;
-use bevy_rand::prelude::*;
-use rand_core::RngCore;
+use bevy_jam_4::headless::{headless_ticks_from_args, HeadlessPlugin};
+use bevy_jam_4::util::arg_value;
+use bevy_jam_4::{PresentationPlugin, SimulationPlugin, DEFAULT_TICK_RATE_HZ};
 
-mod behavior;
-mod camera;
-mod components;
-mod resources;
-mod sprite;
-mod util;
-mod world_map;
-mod world_ui;
-
-use components::*;
-use resources::FoodRes;
-use util::*;
-
-const BACKGROUND_COLOR: Color = Color::rgb(0.1, 0.1, 0.1);
-const PLAYER_COLOR: Color = Color::rgb(1.0, 0.5, 0.5);
-
-const ANT_SIZE: Vec3 = Vec3::new(30.0, 30.0, 0.0);
-
-fn setup(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
-) {
-    commands.insert_resource(FoodRes::default());
-
-    // Player
-    commands.spawn((
-        MaterialMesh2dBundle {
-            mesh: meshes.add(shape::Circle::default().into()).into(),
-            material: materials.add(ColorMaterial::from(PLAYER_COLOR)),
-            transform: Transform::from_translation(world_map::world_map_center_3d())
-                .with_scale(ANT_SIZE),
-            ..default()
-        },
-        Player,
-    ));
-
-    for _ in 0..4 {
-        let transform = Transform::from_translation(Vec3::new(
-            (rng.next_u32() as i32 % 500) as f32 + world_map::world_map_center().x,
-            (rng.next_u32() as i32 % 300) as f32 + world_map::world_map_center().y,
-            1.0,
-        ))
-        .with_scale(ANT_SIZE);
-
-        commands.spawn((
-            MaterialMesh2dBundle {
-                mesh: meshes.add(shape::Circle::default().into()).into(),
-                material: materials.add(ColorMaterial::from(Color::ORANGE_RED)),
-                transform,
-                ..default()
-            },
-            Food,
-        ));
+fn main() {
+    let tick_rate_hz = arg_value("--tick-rate").unwrap_or(DEFAULT_TICK_RATE_HZ);
+    if !tick_rate_hz.is_finite() || tick_rate_hz <= 0.0 {
+        eprintln!("--tick-rate must be a positive number of ticks per second, got {tick_rate_hz}");
+        std::process::exit(1);
     }
 
-    for _ in 0..2000 {
-        commands.spawn(behavior::AntBundle {
-            ant: Ant::default(),
-            transform: Transform::default().with_translation(Vec3::new(
-                world_map::world_map_center().x,
-                world_map::world_map_center().y,
-                0.,
-            )),
-            rng: rng.fork_rng(),
-        });
+    let mut app = App::new();
+    let simulation = SimulationPlugin {
+        tick_rate_hz,
+        seed: arg_value("--seed"),
+        ..default()
+    };
+
+    if let Some(ticks) = headless_ticks_from_args() {
+        app.add_plugins(MinimalPlugins)
+            .add_plugins(HeadlessPlugin {
+                ticks,
+                tick_rate_hz: simulation.tick_rate_hz,
+            })
+            .add_plugins(simulation);
+    } else {
+        app.add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
+            .add_plugins(simulation)
+            .add_plugins(PresentationPlugin);
     }
-}
 
-fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
-        .add_plugins(EntropyPlugin::<ChaCha8Rng>::default())
-        .add_plugins(camera::CameraPlugin)
-        .add_plugins(sprite::AnimationTestPlugin)
-        .add_plugins(world_map::WorldMapPlugin)
-        .add_plugins(world_ui::WorldUIPlugin)
-        .insert_resource(ClearColor(BACKGROUND_COLOR))
-        .add_systems(Startup, setup)
-        .add_systems(Update, bevy::window::close_on_esc)
-        .add_systems(Startup, behavior::setup_pher_tiles)
-        .add_systems(
-            Update,
-            (
-                behavior::update_ant_movement,
-                behavior::spawn_pheromones,
-                behavior::decay_pheromones,
-            ),
-        )
-        .add_systems(
-            Update,
-            (
-                // behavior::debug_ants,
-                behavior::debug_phers,
-                behavior::debug_ants_minimal,
-            ),
-        )
-        .add_event::<MouseWheel>()
-        .run();
+    app.run();
 }
@@ -0,0 +1,102 @@
+use bevy::prelude::*;
+
+use crate::components::*;
+use crate::economy::*;
+use crate::flow_field::FlowTarget;
+use crate::lifecycle::spawn_corpse;
+use crate::rival::RivalColony;
+use crate::world_map::*;
+
+impl Need {
+    // Where ants go to see to the need
+    pub fn flow_target(&self) -> FlowTarget {
+        match self {
+            Need::Food => FlowTarget::FoodStorage,
+            Need::Rest => FlowTarget::Chamber,
+        }
+    }
+}
+
+// A full ant goes hungry over this many seconds, and a rested one tired over this many
+const SATIETY_DURATION: f32 = 600.0;
+const ENERGY_DURATION: f32 = 300.0;
+// Seconds of rest from empty to fully rested
+const REST_DURATION: f32 = 30.0;
+// An ant with nothing left in its stomach tires this many times faster
+const STARVING_FATIGUE_FACTOR: f32 = 2.0;
+const HUNGRY_BELOW: f32 = 0.3;
+const TIRED_BELOW: f32 = 0.2;
+// Food one meal takes from the stock, so an ant eats `ANT_FOOD_PER_SECOND` on average
+const MEAL_FOOD: f32 = ANT_FOOD_PER_SECOND * SATIETY_DURATION;
+
+// Drains every ant's needs, feeds the hungry ones standing at food storage, rests the tired
+// ones standing in a chamber and sends off any that need to go looking. What counts as
+// either is down to the ant's colony, as `FlowTarget::is_goal` says.
+pub fn update_needs(
+    mut commands: Commands,
+    mut ants: Query<(Entity, &Transform, &OnZLevel, &Colony, &mut Needs), With<Ant>>,
+    map: WorldMap,
+    mut upkeep: ResMut<FoodUpkeep>,
+    mut rival: ResMut<RivalColony>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+
+    for (entity, ant_trans, ant_level, colony, mut needs) in ants.iter_mut() {
+        needs.satiety = (needs.satiety - dt / SATIETY_DURATION).max(0.0);
+        if needs.is_resting && needs.satiety > 0.0 {
+            needs.energy = (needs.energy + dt / REST_DURATION).min(1.0);
+        } else {
+            let fatigue = if needs.satiety > 0.0 {
+                1.0
+            } else {
+                STARVING_FATIGUE_FACTOR
+            };
+            needs.energy = (needs.energy - fatigue * dt / ENERGY_DURATION).max(0.0);
+        }
+
+        let position = ant_trans.translation.xy();
+        if needs.satiety <= 0.0 && needs.energy <= 0.0 {
+            commands.entity(entity).despawn();
+            spawn_corpse(&mut commands, position, ant_level.0);
+            continue;
+        }
+
+        let tile = (position.x >= 0. && position.y >= 0.)
+            .then(|| world_pos_to_two_d_index(position))
+            .and_then(|tile| Some((tile, map.building_at(ant_level.0, tile)?)));
+        let can_see_to = |need: Need| {
+            tile.map_or(false, |(tile, building)| {
+                need.flow_target()
+                    .is_goal(*colony, &rival, ant_level.0, tile, building)
+            })
+        };
+        let (can_eat, can_rest) = (can_see_to(Need::Food), can_see_to(Need::Rest));
+
+        // the ant has nothing to eat while its colony is starving
+        if can_eat && needs.satiety < HUNGRY_BELOW {
+            let colony_upkeep = if *colony == Colony::PLAYER {
+                &mut *upkeep
+            } else {
+                &mut rival.upkeep
+            };
+            if !colony_upkeep.is_starving {
+                colony_upkeep.charge(MEAL_FOOD);
+                needs.satiety = 1.0;
+            }
+        }
+
+        needs.is_resting = can_rest
+            && needs.satiety > 0.0
+            && needs.energy < 1.0
+            && (needs.is_resting || needs.energy < TIRED_BELOW);
+
+        needs.seeking = if needs.satiety < HUNGRY_BELOW {
+            Some(Need::Food)
+        } else if needs.energy < TIRED_BELOW && !needs.is_resting {
+            Some(Need::Rest)
+        } else {
+            None
+        };
+    }
+}
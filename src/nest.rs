@@ -0,0 +1,112 @@
+use std::collections::{HashMap, VecDeque};
+
+use bevy::prelude::*;
+
+use crate::world_map::*;
+
+/// Where returning ants take their food, worked out from the `FoodStorage` tiles of each
+/// `ZLevel`. Kept up to date whenever a level's layout changes.
+#[derive(Resource, Default)]
+pub struct NestTargets {
+    levels: HashMap<i32, LevelTargets>,
+}
+
+struct LevelTargets {
+    // Connected area of walkable tiles each tile belongs to, indexed like `ZLevel::tiles`
+    regions: Vec<Option<u32>>,
+    food_storage: Vec<(Vec2, u32)>, // world space tile center and region
+}
+
+impl NestTargets {
+    /// Center of the nearest `FoodStorage` tile that can be walked to from `world_pos`,
+    /// or `world_map_center()` if there is none.
+    pub fn drop_off(&self, level: i32, world_pos: Vec2) -> Vec2 {
+        self.nearest_food_storage(level, world_pos)
+            .unwrap_or_else(world_map_center)
+    }
+
+    pub fn nearest_food_storage(&self, level: i32, world_pos: Vec2) -> Option<Vec2> {
+        let targets = self.levels.get(&level)?;
+        if world_pos.x < 0. || world_pos.y < 0. {
+            return None;
+        }
+        let region = two_d_index_to_one_d_index(world_pos_to_two_d_index(world_pos))
+            .and_then(|i| targets.regions[i])?;
+
+        targets
+            .food_storage
+            .iter()
+            .filter(|(_, storage_region)| *storage_region == region)
+            .map(|(storage_pos, _)| *storage_pos)
+            .min_by(|a, b| {
+                a.distance_squared(world_pos)
+                    .total_cmp(&b.distance_squared(world_pos))
+            })
+    }
+}
+
+pub fn update_nest_targets(
+    changed_z_levels: Query<&ZLevel, Changed<ZLevel>>,
+    mut targets: ResMut<NestTargets>,
+) {
+    for z_level in changed_z_levels.iter() {
+        let regions = walkable_regions(z_level);
+        let food_storage = z_level
+            .tiles
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| tile.building == BuildingType::FoodStorage)
+            .filter_map(|(i, _)| {
+                let tile_center = (one_d_index_to_two_d_index(i).as_vec2() + 0.5) * TILE_SIZE;
+                regions[i].map(|region| (tile_center, region))
+            })
+            .collect();
+
+        targets.levels.insert(
+            z_level.z_level,
+            LevelTargets {
+                regions,
+                food_storage,
+            },
+        );
+    }
+}
+
+// Flood fills the walkable tiles, giving every connected area its own number
+fn walkable_regions(z_level: &ZLevel) -> Vec<Option<u32>> {
+    let mut regions = vec![None; z_level.tiles.len()];
+    let mut next_region = 0;
+    let mut open = VecDeque::new();
+
+    for start in 0..z_level.tiles.len() {
+        if regions[start].is_some() || !z_level.is_tile_walkable(one_d_index_to_two_d_index(start))
+        {
+            continue;
+        }
+
+        regions[start] = Some(next_region);
+        open.push_back(start);
+
+        while let Some(i) = open.pop_front() {
+            let tile = one_d_index_to_two_d_index(i).as_ivec2();
+            for offset in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+                let neighbour = tile + offset;
+                if neighbour.x < 0 || neighbour.y < 0 {
+                    continue;
+                }
+                let neighbour = neighbour.as_uvec2();
+                let Some(j) = two_d_index_to_one_d_index(neighbour) else {
+                    continue;
+                };
+                if regions[j].is_none() && z_level.is_tile_walkable(neighbour) {
+                    regions[j] = Some(next_region);
+                    open.push_back(j);
+                }
+            }
+        }
+
+        next_region += 1;
+    }
+
+    regions
+}
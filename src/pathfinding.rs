@@ -0,0 +1,302 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use bevy::prelude::*;
+
+use crate::world_map::*;
+
+// Step costs, scaled so diagonals stay integers
+pub const STRAIGHT_COST: u32 = 10;
+pub const DIAGONAL_COST: u32 = 14;
+
+/// Shortest path of tiles from `start` to `goal` over the walkable tiles of `z_level`,
+/// both ends included. Moves in 8 directions, but never cuts a corner: a diagonal step
+/// is only allowed when both tiles it passes between are walkable too.
+pub fn find_path(z_level: &ZLevel, start: UVec2, goal: UVec2) -> Option<Vec<UVec2>> {
+    let start_index = two_d_index_to_one_d_index(start)?;
+    let goal_index = two_d_index_to_one_d_index(goal)?;
+    if !z_level.is_tile_walkable(start) || !z_level.is_tile_walkable(goal) {
+        return None;
+    }
+
+    let mut cost_so_far = vec![u32::MAX; MAP_DATA_SIZE];
+    let mut came_from: Vec<Option<usize>> = vec![None; MAP_DATA_SIZE];
+    let mut open = BinaryHeap::new();
+
+    cost_so_far[start_index] = 0;
+    open.push(Reverse((octile_distance(start, goal), start_index)));
+
+    while let Some(Reverse((_, i))) = open.pop() {
+        if i == goal_index {
+            return Some(walk_back(&came_from, goal_index));
+        }
+
+        let tile = one_d_index_to_two_d_index(i);
+        for (neighbour, step_cost) in walkable_neighbours(z_level, tile) {
+            let j = two_d_index_to_one_d_index(neighbour).unwrap();
+            let cost = cost_so_far[i] + step_cost;
+            if cost >= cost_so_far[j] {
+                continue;
+            }
+
+            cost_so_far[j] = cost;
+            came_from[j] = Some(i);
+            open.push(Reverse((cost + octile_distance(neighbour, goal), j)));
+        }
+    }
+
+    None
+}
+
+// Tiles a single step away from `tile` that can be walked to, with the cost of the step
+pub fn walkable_neighbours(
+    z_level: &ZLevel,
+    tile: UVec2,
+) -> impl Iterator<Item = (UVec2, u32)> + '_ {
+    let is_walkable = move |offset: IVec2| {
+        let pos = tile.as_ivec2() + offset;
+        pos.x >= 0 && pos.y >= 0 && z_level.is_tile_walkable(pos.as_uvec2())
+    };
+
+    [
+        IVec2::new(1, 0),
+        IVec2::new(-1, 0),
+        IVec2::new(0, 1),
+        IVec2::new(0, -1),
+        IVec2::new(1, 1),
+        IVec2::new(1, -1),
+        IVec2::new(-1, 1),
+        IVec2::new(-1, -1),
+    ]
+    .into_iter()
+    .filter(move |offset| {
+        if !is_walkable(*offset) {
+            return false;
+        }
+        // no cutting corners
+        offset.x == 0
+            || offset.y == 0
+            || (is_walkable(IVec2::new(offset.x, 0)) && is_walkable(IVec2::new(0, offset.y)))
+    })
+    .map(move |offset| {
+        let step_cost = if offset.x != 0 && offset.y != 0 {
+            DIAGONAL_COST
+        } else {
+            STRAIGHT_COST
+        };
+        ((tile.as_ivec2() + offset).as_uvec2(), step_cost)
+    })
+}
+
+fn octile_distance(a: UVec2, b: UVec2) -> u32 {
+    let dx = a.x.abs_diff(b.x);
+    let dy = a.y.abs_diff(b.y);
+    STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+}
+
+fn walk_back(came_from: &[Option<usize>], goal_index: usize) -> Vec<UVec2> {
+    let mut path = vec![one_d_index_to_two_d_index(goal_index)];
+    let mut current = goal_index;
+    while let Some(previous) = came_from[current] {
+        path.push(one_d_index_to_two_d_index(previous));
+        current = previous;
+    }
+    path.reverse();
+    path
+}
+
+/// Paths found so far, keyed by level, start and goal tile. Every path on a level is
+/// dropped whenever that `ZLevel` changes, and the whole cache starts over once it holds
+/// `PATH_CACHE_CAPACITY` of them.
+#[derive(Resource, Default)]
+pub struct PathCache {
+    paths: HashMap<(i32, UVec2, UVec2), Option<Vec<UVec2>>>, // `None` for no way through
+}
+
+// Enough for a few jobs' worth of paths for every ant at once
+const PATH_CACHE_CAPACITY: usize = 4096;
+
+impl PathCache {
+    pub fn find_path(&mut self, z_level: &ZLevel, start: UVec2, goal: UVec2) -> Option<&[UVec2]> {
+        let key = (z_level.z_level, start, goal);
+        if self.paths.len() >= PATH_CACHE_CAPACITY && !self.paths.contains_key(&key) {
+            self.paths.clear();
+        }
+
+        self.paths
+            .entry(key)
+            .or_insert_with(|| find_path(z_level, start, goal))
+            .as_deref()
+    }
+
+    pub fn invalidate_level(&mut self, level: i32) {
+        self.paths
+            .retain(|(path_level, _, _), _| *path_level != level);
+    }
+}
+
+// Runs in the fixed timestep ahead of anything asking for paths. Change detection sees
+// every change since it last ran, while a `TilesChanged` sent during one tick can be
+// dropped before the next when a frame goes by without a tick.
+pub fn invalidate_paths(mut cache: ResMut<PathCache>, changed: Query<&ZLevel, Changed<ZLevel>>) {
+    for z_level in changed.iter() {
+        cache.invalidate_level(z_level.z_level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A level of solid soil with only `open` dug out
+    fn level_with(z: i32, open: &[URect]) -> ZLevel {
+        let mut z_level = ZLevel::with_level(z);
+        for area in open {
+            z_level.set_area(*area, BuildingType::Tunnel);
+        }
+        z_level
+    }
+
+    fn rect(min: (u32, u32), max: (u32, u32)) -> URect {
+        URect::new(min.0, min.1, max.0, max.1)
+    }
+
+    fn path_cost(path: &[UVec2]) -> u32 {
+        path.windows(2)
+            .map(|step| {
+                if step[0].x != step[1].x && step[0].y != step[1].y {
+                    DIAGONAL_COST
+                } else {
+                    STRAIGHT_COST
+                }
+            })
+            .sum()
+    }
+
+    #[test]
+    fn no_path_to_solid_or_walled_off_goal() {
+        let z_level = level_with(0, &[rect((0, 0), (3, 3)), rect((5, 0), (6, 1))]);
+
+        // goal is solid soil
+        assert!(find_path(&z_level, UVec2::new(0, 0), UVec2::new(4, 0)).is_none());
+        // goal is dug out, but nothing connects it to the start
+        assert!(find_path(&z_level, UVec2::new(0, 0), UVec2::new(5, 0)).is_none());
+        // off the map
+        assert!(find_path(&z_level, UVec2::new(0, 0), MAP_SIZE).is_none());
+    }
+
+    #[test]
+    fn never_cuts_corners() {
+        // two tiles touching only at their corners
+        let z_level = level_with(0, &[rect((0, 0), (1, 1)), rect((1, 1), (2, 2))]);
+        assert!(find_path(&z_level, UVec2::new(0, 0), UVec2::new(1, 1)).is_none());
+
+        // once one side is open too it has to go around it
+        let z_level = level_with(
+            0,
+            &[
+                rect((0, 0), (1, 1)),
+                rect((1, 1), (2, 2)),
+                rect((1, 0), (2, 1)),
+            ],
+        );
+        let path = find_path(&z_level, UVec2::new(0, 0), UVec2::new(1, 1)).unwrap();
+        assert_eq!(
+            path,
+            vec![UVec2::new(0, 0), UVec2::new(1, 0), UVec2::new(1, 1)]
+        );
+    }
+
+    #[test]
+    fn open_ground_path_costs_the_octile_distance() {
+        let z_level = level_with(0, &[rect((0, 0), (10, 10))]);
+        let (start, goal) = (UVec2::new(1, 2), UVec2::new(8, 5));
+
+        let path = find_path(&z_level, start, goal).unwrap();
+        assert_eq!(path.first(), Some(&start));
+        assert_eq!(path.last(), Some(&goal));
+        assert_eq!(path_cost(&path), octile_distance(start, goal));
+    }
+
+    #[test]
+    fn path_around_a_wall_is_shortest() {
+        // a wall across the middle with a gap at the top
+        let z_level = level_with(
+            0,
+            &[
+                rect((0, 0), (5, 7)),
+                rect((6, 0), (11, 7)),
+                rect((5, 6), (6, 7)),
+            ],
+        );
+
+        let path = find_path(&z_level, UVec2::new(0, 0), UVec2::new(10, 0)).unwrap();
+        // up to the gap, through it and back down, diagonally wherever it can
+        let (to_gap, from_gap) = (
+            octile_distance(UVec2::new(0, 0), UVec2::new(4, 6)),
+            octile_distance(UVec2::new(6, 6), UVec2::new(10, 0)),
+        );
+        assert_eq!(path_cost(&path), to_gap + 2 * STRAIGHT_COST + from_gap);
+        assert!(path.iter().all(|tile| z_level.is_tile_walkable(*tile)));
+    }
+
+    #[test]
+    fn cache_is_dropped_when_the_level_changes() {
+        let mut app = App::new();
+        app.init_resource::<PathCache>()
+            .add_systems(Update, invalidate_paths);
+
+        let (start, goal) = (UVec2::new(0, 0), UVec2::new(4, 0));
+        let level = app.world.spawn(level_with(0, &[rect((0, 0), (5, 1))])).id();
+        let other_level = app
+            .world
+            .spawn(level_with(-1, &[rect((0, 0), (5, 1))]))
+            .id();
+        // both levels count as changed on the first run
+        app.update();
+        app.world
+            .resource_scope(|world, mut cache: Mut<PathCache>| {
+                for entity in [level, other_level] {
+                    let z_level = world.get::<ZLevel>(entity).unwrap();
+                    assert!(cache.find_path(z_level, start, goal).is_some());
+                }
+            });
+
+        // fill the tunnel in, the cached path is stale until the systems run
+        app.world
+            .get_mut::<ZLevel>(level)
+            .unwrap()
+            .set_area(rect((2, 0), (3, 1)), BuildingType::None);
+        app.world
+            .resource_scope(|world, mut cache: Mut<PathCache>| {
+                let z_level = world.get::<ZLevel>(level).unwrap();
+                assert!(cache.find_path(z_level, start, goal).is_some());
+            });
+        app.update();
+
+        app.world
+            .resource_scope(|world, mut cache: Mut<PathCache>| {
+                let z_level = world.get::<ZLevel>(level).unwrap();
+                assert!(cache.find_path(z_level, start, goal).is_none());
+                // other levels keep their paths
+                assert!(cache.paths.contains_key(&(-1, start, goal)));
+            });
+    }
+
+    #[test]
+    fn cache_never_grows_past_its_capacity() {
+        let levels = [
+            level_with(0, &[rect((0, 0), (MAP_SIZE.x, MAP_SIZE.y))]),
+            level_with(-1, &[rect((0, 0), (MAP_SIZE.x, MAP_SIZE.y))]),
+        ];
+        let mut cache = PathCache::default();
+
+        for z_level in levels.iter() {
+            for i in 0..MAP_DATA_SIZE {
+                let tile = one_d_index_to_two_d_index(i);
+                assert!(cache.find_path(z_level, tile, tile).is_some());
+                assert!(cache.paths.len() <= PATH_CACHE_CAPACITY);
+            }
+        }
+    }
+}
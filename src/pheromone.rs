@@ -0,0 +1,486 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use strum::{EnumCount, IntoEnumIterator};
+use strum_macros::{EnumCount, EnumIter};
+
+use crate::behavior::AntState;
+use crate::components::{Caste, Colony, COLONY_COUNT};
+use crate::world_map::*;
+
+// Each map tile is split into this many field cells along each axis
+pub const PHEROMONE_CELLS_PER_TILE: u32 = 4;
+pub const PHEROMONE_FIELD_SIZE: UVec2 = UVec2::new(
+    MAP_SIZE.x * PHEROMONE_CELLS_PER_TILE,
+    MAP_SIZE.y * PHEROMONE_CELLS_PER_TILE,
+);
+const PHEROMONE_FIELD_DATA_SIZE: usize = (PHEROMONE_FIELD_SIZE.x * PHEROMONE_FIELD_SIZE.y) as usize;
+
+pub fn pheromone_cell_size() -> Vec2 {
+    TILE_SIZE / PHEROMONE_CELLS_PER_TILE as f32
+}
+
+// Cells weaker than this are cleared so the field stays sparse enough to draw
+const PHEROMONE_MIN_INTENSITY: f32 = 0.01;
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, EnumIter, EnumCount)]
+pub enum PheromoneKind {
+    HomeThisWay,
+    FoodThisWay,
+    Alarm,         // dropped by ants under attack
+    RecruitDig,    // spread around construction sites
+    QueenPresence, // spread around the queen's chamber
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PheromoneKindSettings {
+    pub evaporation: f32, // fraction of intensity lost per second
+    pub diffusion: f32,   // fraction of intensity exchanged with neighbouring cells per second
+    pub cap: f32,         // max intensity of a single cell
+}
+
+impl PheromoneKind {
+    pub fn default_settings(&self) -> PheromoneKindSettings {
+        match self {
+            PheromoneKind::HomeThisWay => PheromoneKindSettings {
+                evaporation: 0.05,
+                diffusion: 0.1,
+                cap: 20.0,
+            },
+            PheromoneKind::FoodThisWay => PheromoneKindSettings {
+                evaporation: 0.1,
+                diffusion: 0.2,
+                cap: 10.0,
+            },
+            PheromoneKind::Alarm => PheromoneKindSettings {
+                evaporation: 0.5,
+                diffusion: 0.5,
+                cap: 10.0,
+            },
+            PheromoneKind::RecruitDig => PheromoneKindSettings {
+                evaporation: 0.2,
+                diffusion: 0.3,
+                cap: 10.0,
+            },
+            PheromoneKind::QueenPresence => PheromoneKindSettings {
+                evaporation: 0.3,
+                diffusion: 0.4,
+                cap: 5.0,
+            },
+        }
+    }
+}
+
+/// How strongly an ant in a given state steers along each kind of pheromone. Positive
+/// weights attract, negative weights repel and anything missing is ignored. Per caste
+/// weights take precedence over the ones shared by every caste.
+#[derive(Resource)]
+pub struct PheromoneResponses {
+    weights: HashMap<(AntState, PheromoneKind), f32>,
+    caste_weights: HashMap<(Caste, AntState, PheromoneKind), f32>,
+}
+
+impl Default for PheromoneResponses {
+    fn default() -> Self {
+        Self {
+            weights: HashMap::from([
+                ((AntState::Wandering, PheromoneKind::FoodThisWay), 1.0),
+                ((AntState::Wandering, PheromoneKind::RecruitDig), 0.5),
+                ((AntState::Wandering, PheromoneKind::Alarm), -0.5),
+                ((AntState::HasFood, PheromoneKind::HomeThisWay), 1.0),
+                ((AntState::HasFood, PheromoneKind::QueenPresence), 0.3),
+                ((AntState::HasFood, PheromoneKind::Alarm), -0.5),
+            ]),
+            caste_weights: HashMap::from([
+                (
+                    (Caste::Soldier, AntState::Wandering, PheromoneKind::Alarm),
+                    1.5,
+                ),
+                (
+                    (
+                        Caste::Digger,
+                        AntState::Wandering,
+                        PheromoneKind::RecruitDig,
+                    ),
+                    1.5,
+                ),
+                (
+                    (
+                        Caste::Nurse,
+                        AntState::Wandering,
+                        PheromoneKind::QueenPresence,
+                    ),
+                    0.5,
+                ),
+            ]),
+        }
+    }
+}
+
+impl PheromoneResponses {
+    pub fn weight(&self, caste: Caste, state: AntState, kind: PheromoneKind) -> f32 {
+        self.caste_weights
+            .get(&(caste, state, kind))
+            .or_else(|| self.weights.get(&(state, kind)))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    pub fn set_weight(&mut self, state: AntState, kind: PheromoneKind, weight: f32) {
+        self.weights.insert((state, kind), weight);
+    }
+
+    pub fn set_caste_weight(
+        &mut self,
+        caste: Caste,
+        state: AntState,
+        kind: PheromoneKind,
+        weight: f32,
+    ) {
+        self.caste_weights.insert((caste, state, kind), weight);
+    }
+}
+
+/// Deposits `rate` of `kind` per second wherever the entity's `Transform` is, on the
+/// level given by its `OnZLevel` (level 0 without one).
+#[derive(Component)]
+pub struct PheromoneEmitter {
+    pub kind: PheromoneKind,
+    pub rate: f32,
+}
+
+/// Sent when an ant is attacked, drops a burst of `colony`'s `PheromoneKind::Alarm` at
+/// `position` on `z_level`.
+#[derive(Event)]
+pub struct AlarmRaised {
+    pub position: Vec2,
+    pub z_level: i32,
+    pub colony: Colony,
+}
+
+/// Evaporation, diffusion and cap for each `PheromoneKind`.
+#[derive(Resource)]
+pub struct PheromoneSettings {
+    kinds: Vec<PheromoneKindSettings>, // indexed by `PheromoneKind as usize`
+}
+
+impl Default for PheromoneSettings {
+    fn default() -> Self {
+        Self {
+            kinds: PheromoneKind::iter()
+                .map(|kind| kind.default_settings())
+                .collect(),
+        }
+    }
+}
+
+impl PheromoneSettings {
+    pub fn get(&self, kind: PheromoneKind) -> &PheromoneKindSettings {
+        &self.kinds[kind as usize]
+    }
+
+    pub fn get_mut(&mut self, kind: PheromoneKind) -> &mut PheromoneKindSettings {
+        &mut self.kinds[kind as usize]
+    }
+}
+
+/// Pheromone intensity for every kind, stored as one dense grid per kind and colony so
+/// each colony only ever smells its own. Lives on the same entity as the `ZLevel` it
+/// belongs to, and is told which of its cells are walkable whenever that level changes.
+#[derive(Component)]
+pub struct PheromoneField {
+    layers: Vec<Vec<f32>>, // indexed by `layer_index`, then by cell
+    // Layers with any pheromone at all, the rest are skipped by `update`
+    non_empty: Vec<bool>,
+    walkable_cells: Vec<bool>,
+    scratch: Vec<f32>,
+}
+
+impl Default for PheromoneField {
+    fn default() -> Self {
+        Self {
+            layers: (0..COLONY_COUNT * PheromoneKind::COUNT)
+                .map(|_| vec![0.0; PHEROMONE_FIELD_DATA_SIZE])
+                .collect(),
+            non_empty: vec![false; COLONY_COUNT * PheromoneKind::COUNT],
+            walkable_cells: vec![false; PHEROMONE_FIELD_DATA_SIZE],
+            scratch: Vec::with_capacity(PHEROMONE_FIELD_DATA_SIZE),
+        }
+    }
+}
+
+fn layer_index(colony: Colony, kind: PheromoneKind) -> usize {
+    colony.0 as usize * PheromoneKind::COUNT + kind as usize
+}
+
+impl PheromoneField {
+    pub fn deposit(&mut self, colony: Colony, kind: PheromoneKind, world_pos: Vec2, amount: f32) {
+        if let Some(i) = world_pos_to_cell_index(world_pos) {
+            let i_layer = layer_index(colony, kind);
+            self.layers[i_layer][i] += amount;
+            self.non_empty[i_layer] |= amount > 0.0;
+        }
+    }
+
+    // Takes the level's walkability, indexed like `ZLevel::tiles`, down to every cell
+    pub fn set_walkable_tiles(&mut self, walkable_tiles: &[bool]) {
+        for (i, walkable) in self.walkable_cells.iter_mut().enumerate() {
+            let cell = cell_index_to_cell(i);
+            *walkable = two_d_index_to_one_d_index(cell / PHEROMONE_CELLS_PER_TILE)
+                .map_or(false, |i_tile| walkable_tiles[i_tile]);
+        }
+    }
+
+    pub fn sample(&self, colony: Colony, kind: PheromoneKind, world_pos: Vec2) -> f32 {
+        match world_pos_to_cell_index(world_pos) {
+            Some(i) => self.layers[layer_index(colony, kind)][i],
+            None => 0.0,
+        }
+    }
+
+    // What anything outside the colonies smells, every colony's `kind` at once
+    pub fn sample_all_colonies(&self, kind: PheromoneKind, world_pos: Vec2) -> f32 {
+        Colony::all()
+            .map(|colony| self.sample(colony, kind, world_pos))
+            .sum()
+    }
+
+    // Diffuses, evaporates and caps every colony's `kind` over `dt` seconds
+    pub fn update(&mut self, kind: PheromoneKind, settings: &PheromoneKindSettings, dt: f32) {
+        let keep = (1.0 - settings.evaporation * dt).max(0.0);
+
+        for colony in Colony::all() {
+            let i_layer = layer_index(colony, kind);
+            if !self.non_empty[i_layer] {
+                continue;
+            }
+            self.diffuse(i_layer, settings.diffusion * dt);
+
+            let mut non_empty = false;
+            for intensity in self.layers[i_layer].iter_mut() {
+                *intensity = (*intensity * keep).min(settings.cap);
+                if *intensity < PHEROMONE_MIN_INTENSITY {
+                    *intensity = 0.0;
+                }
+                non_empty |= *intensity > 0.0;
+            }
+            self.non_empty[i_layer] = non_empty;
+        }
+    }
+
+    // Moves `amount` of the difference between each cell and its four neighbours across.
+    // Cells on tiles that aren't walkable neither give nor receive anything.
+    fn diffuse(&mut self, i_layer: usize, amount: f32) {
+        let amount = amount.clamp(0.0, 1.0);
+        if amount == 0.0 {
+            return;
+        }
+
+        let layer = &self.layers[i_layer];
+        let walkable = &self.walkable_cells;
+        let scratch = &mut self.scratch;
+        scratch.clear();
+        scratch.extend_from_slice(layer);
+
+        for y in 0..PHEROMONE_FIELD_SIZE.y {
+            for x in 0..PHEROMONE_FIELD_SIZE.x {
+                let i = cell_to_index(UVec2::new(x, y));
+                if !walkable[i] {
+                    continue;
+                }
+
+                let mut flow = 0.0;
+
+                for neighbour in cell_neighbours(x, y) {
+                    if walkable[neighbour] {
+                        flow += layer[neighbour] - layer[i];
+                    }
+                }
+
+                scratch[i] += flow * amount / 4.0;
+            }
+        }
+
+        std::mem::swap(&mut self.layers[i_layer], &mut self.scratch);
+    }
+
+    // Every colony's `kind` added up
+    pub fn total(&self, kind: PheromoneKind) -> f32 {
+        Colony::all()
+            .map(|colony| self.layers[layer_index(colony, kind)].iter().sum::<f32>())
+            .sum()
+    }
+
+    // World space center and intensity of every cell of `colony`'s `kind` that holds any
+    // pheromone
+    pub fn iter_cells(
+        &self,
+        colony: Colony,
+        kind: PheromoneKind,
+    ) -> impl Iterator<Item = (Vec2, f32)> + '_ {
+        self.layers[layer_index(colony, kind)]
+            .iter()
+            .enumerate()
+            .filter(|(_, intensity)| **intensity > 0.0)
+            .map(|(i, intensity)| (cell_index_to_world_pos(i), *intensity))
+    }
+}
+
+fn world_pos_to_cell_index(world_pos: Vec2) -> Option<usize> {
+    if world_pos.x < 0. || world_pos.y < 0. {
+        return None;
+    }
+
+    let cell = (world_pos / pheromone_cell_size()).as_uvec2();
+    if cell.x < PHEROMONE_FIELD_SIZE.x && cell.y < PHEROMONE_FIELD_SIZE.y {
+        Some(cell_to_index(cell))
+    } else {
+        None
+    }
+}
+
+fn cell_to_index(cell: UVec2) -> usize {
+    (cell.y * PHEROMONE_FIELD_SIZE.x + cell.x) as usize
+}
+
+// Indices of the cells left, right, below and above the cell at `x`, `y` that are on the field
+fn cell_neighbours(x: u32, y: u32) -> impl Iterator<Item = usize> {
+    let i = cell_to_index(UVec2::new(x, y));
+    let width = PHEROMONE_FIELD_SIZE.x as usize;
+    [
+        (x > 0).then(|| i - 1),
+        (x + 1 < PHEROMONE_FIELD_SIZE.x).then(|| i + 1),
+        (y > 0).then(|| i - width),
+        (y + 1 < PHEROMONE_FIELD_SIZE.y).then(|| i + width),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+fn cell_index_to_cell(index: usize) -> UVec2 {
+    UVec2::new(
+        index as u32 % PHEROMONE_FIELD_SIZE.x,
+        index as u32 / PHEROMONE_FIELD_SIZE.x,
+    )
+}
+
+fn cell_index_to_world_pos(index: usize) -> Vec2 {
+    (cell_index_to_cell(index).as_vec2() + 0.5) * pheromone_cell_size()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_WALKABLE: [bool; MAP_DATA_SIZE] = [true; MAP_DATA_SIZE];
+
+    fn settings(evaporation: f32, diffusion: f32) -> PheromoneKindSettings {
+        PheromoneKindSettings {
+            evaporation,
+            diffusion,
+            cap: f32::MAX,
+        }
+    }
+
+    fn tile_center(x: u32, y: u32) -> Vec2 {
+        (UVec2::new(x, y).as_vec2() + 0.5) * TILE_SIZE
+    }
+
+    #[test]
+    fn deposits_only_reach_their_own_colony() {
+        let mut field = PheromoneField::default();
+        let position = tile_center(10, 10);
+        field.deposit(Colony::PLAYER, PheromoneKind::FoodThisWay, position, 3.0);
+        field.deposit(Colony::RIVAL, PheromoneKind::FoodThisWay, position, 2.0);
+
+        assert_eq!(
+            field.sample(Colony::PLAYER, PheromoneKind::FoodThisWay, position),
+            3.0
+        );
+        assert_eq!(
+            field.sample(Colony::RIVAL, PheromoneKind::FoodThisWay, position),
+            2.0
+        );
+        assert_eq!(
+            field.sample(Colony::PLAYER, PheromoneKind::HomeThisWay, position),
+            0.0
+        );
+        assert_eq!(
+            field.sample_all_colonies(PheromoneKind::FoodThisWay, position),
+            5.0
+        );
+        // nothing lands off the map
+        field.deposit(
+            Colony::PLAYER,
+            PheromoneKind::FoodThisWay,
+            Vec2::new(-1.0, 0.0),
+            1.0,
+        );
+        assert_eq!(field.total(PheromoneKind::FoodThisWay), 5.0);
+    }
+
+    #[test]
+    fn evaporation_takes_its_fraction_and_cap_limits_cells() {
+        let mut field = PheromoneField::default();
+        let position = tile_center(10, 10);
+        field.set_walkable_tiles(&ALL_WALKABLE);
+        field.deposit(Colony::PLAYER, PheromoneKind::HomeThisWay, position, 10.0);
+
+        field.update(PheromoneKind::HomeThisWay, &settings(0.1, 0.0), 1.0);
+        let left = field.sample(Colony::PLAYER, PheromoneKind::HomeThisWay, position);
+        assert!((left - 9.0).abs() < 1e-5);
+
+        let capped = PheromoneKindSettings {
+            cap: 4.0,
+            ..settings(0.0, 0.0)
+        };
+        field.update(PheromoneKind::HomeThisWay, &capped, 1.0);
+        assert_eq!(
+            field.sample(Colony::PLAYER, PheromoneKind::HomeThisWay, position),
+            4.0
+        );
+
+        // faint traces are cleared altogether
+        field.update(PheromoneKind::HomeThisWay, &settings(0.999, 0.0), 1.0);
+        assert_eq!(field.total(PheromoneKind::HomeThisWay), 0.0);
+        assert!(!field.non_empty[layer_index(Colony::PLAYER, PheromoneKind::HomeThisWay)]);
+    }
+
+    #[test]
+    fn diffusion_spreads_without_losing_any() {
+        let mut field = PheromoneField::default();
+        let position = tile_center(10, 10);
+        field.set_walkable_tiles(&ALL_WALKABLE);
+        field.deposit(Colony::PLAYER, PheromoneKind::Alarm, position, 10.0);
+
+        for _ in 0..2 {
+            field.update(PheromoneKind::Alarm, &settings(0.0, 0.5), 1.0);
+        }
+
+        assert!((field.total(PheromoneKind::Alarm) - 10.0).abs() < 1e-3);
+        assert!(field.sample(Colony::PLAYER, PheromoneKind::Alarm, position) < 10.0);
+        let neighbour = position + Vec2::new(pheromone_cell_size().x, 0.0);
+        assert!(field.sample(Colony::PLAYER, PheromoneKind::Alarm, neighbour) > 0.0);
+    }
+
+    #[test]
+    fn diffusion_stays_out_of_solid_tiles() {
+        // only a single tile is dug out
+        let mut walkable = [false; MAP_DATA_SIZE];
+        walkable[two_d_index_to_one_d_index(UVec2::new(10, 10)).unwrap()] = true;
+
+        let mut field = PheromoneField::default();
+        field.set_walkable_tiles(&walkable);
+        let position = tile_center(10, 10);
+        field.deposit(Colony::PLAYER, PheromoneKind::Alarm, position, 10.0);
+
+        for _ in 0..20 {
+            field.update(PheromoneKind::Alarm, &settings(0.0, 0.5), 1.0);
+        }
+
+        assert!((field.total(PheromoneKind::Alarm) - 10.0).abs() < 1e-3);
+        for (cell_pos, _) in field.iter_cells(Colony::PLAYER, PheromoneKind::Alarm) {
+            assert_eq!(world_pos_to_two_d_index(cell_pos), UVec2::new(10, 10));
+        }
+    }
+}
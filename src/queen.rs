@@ -0,0 +1,202 @@
+use bevy::math::*;
+use bevy::prelude::*;
+use bevy_prng::ChaCha8Rng;
+use bevy_rand::prelude::*;
+use rand_core::RngCore;
+
+use crate::components::{Combat, Health, OnZLevel};
+use crate::jobs::*;
+use crate::lifecycle::*;
+use crate::resources::*;
+use crate::world_map::*;
+
+/// The colony's only egg layer. Sits in a `QueenChamber` and turns stored food into eggs,
+/// the colony is lost if she dies.
+#[derive(Component)]
+pub struct Queen {
+    pub time_until_egg: f32,
+    pub hunger: f32, // food owed, one unit is taken from the stock whenever it reaches 1
+}
+
+impl Default for Queen {
+    fn default() -> Self {
+        Self {
+            time_until_egg: QUEEN_EGG_INTERVAL,
+            hunger: 0.0,
+        }
+    }
+}
+
+/// Sent once, when the queen dies.
+#[derive(Event)]
+pub struct ColonyLost;
+
+const QUEEN_MAX_HEALTH: f32 = 100.0;
+// She never strikes back, but takes blows better than any ant
+const QUEEN_DEFENCE: f32 = 3.0;
+const QUEEN_EGG_INTERVAL: f32 = 3.0;
+const FOOD_PER_EGG: u64 = 2;
+// Eggs are laid up to this far from the queen
+const QUEEN_LAY_SPREAD: f32 = 8.0;
+const QUEEN_FOOD_PER_SECOND: f32 = 0.2;
+// Health lost per second while starving or outside a queen chamber
+const QUEEN_SUFFER_DAMAGE: f32 = 2.0;
+const QUEEN_HEAL_PER_SECOND: f32 = 0.5;
+// Soldiers the queen keeps posted around her
+const QUEEN_GUARDS: usize = 4;
+const GUARD_JOB_PRIORITY: u32 = 1;
+
+pub fn spawn_queen(commands: &mut Commands, position: Vec2, z_level: i32) -> Entity {
+    commands
+        .spawn((
+            Transform::from_translation(Vec3::new(position.x, position.y, 1.5)),
+            OnZLevel(z_level),
+            Queen::default(),
+            Health::new(QUEEN_MAX_HEALTH),
+            Combat::new(0.0, QUEEN_DEFENCE),
+        ))
+        .id()
+}
+
+pub fn queen_lay_eggs(
+    mut commands: Commands,
+    mut queens: Query<(&Transform, &OnZLevel, &mut Queen)>,
+    mut food: ResMut<FoodRes>,
+    mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
+    map: WorldMap,
+    time: Res<Time>,
+) {
+    for (queen_trans, queen_level, mut queen) in queens.iter_mut() {
+        queen.time_until_egg -= time.delta_seconds();
+        if queen.time_until_egg > 0.0 {
+            continue;
+        }
+
+        // Brood is only kept in chambers that hold it, eggs that would roll out of one are
+        // laid right under her, and away from any she holds them back
+        let holds_brood = |pos: Vec2| {
+            map.z_level(queen_level.0)
+                .and_then(|z_level| z_level.building_at_world_pos(pos))
+                .map_or(false, |building| building.holds_brood())
+        };
+        let offset = Vec2::new(
+            rng.next_u32() as f32 / u32::MAX as f32 * 2.0 - 1.0,
+            rng.next_u32() as f32 / u32::MAX as f32 * 2.0 - 1.0,
+        ) * QUEEN_LAY_SPREAD;
+        let queen_pos = queen_trans.translation.xy();
+        let lay_pos = if holds_brood(queen_pos + offset) {
+            queen_pos + offset
+        } else if holds_brood(queen_pos) {
+            queen_pos
+        } else {
+            continue;
+        };
+
+        // Without enough food she waits until there is
+        if !food.try_consume(FOOD_PER_EGG) {
+            continue;
+        }
+
+        spawn_brood(&mut commands, lay_pos, queen_level.0);
+
+        queen.time_until_egg = QUEEN_EGG_INTERVAL;
+    }
+}
+
+pub fn queen_upkeep(
+    mut queens: Query<(&Transform, &OnZLevel, &mut Queen, &mut Health)>,
+    mut food: ResMut<FoodRes>,
+    map: WorldMap,
+    time: Res<Time>,
+) {
+    for (queen_trans, queen_level, mut queen, mut health) in queens.iter_mut() {
+        queen.hunger += QUEEN_FOOD_PER_SECOND * time.delta_seconds();
+
+        let mut is_starving = false;
+        while queen.hunger >= 1.0 {
+            if !food.try_consume(1) {
+                is_starving = true;
+                break;
+            }
+            queen.hunger -= 1.0;
+        }
+
+        let is_in_chamber = map
+            .z_level(queen_level.0)
+            .and_then(|z_level| z_level.building_at_world_pos(queen_trans.translation.xy()))
+            == Some(BuildingType::QueenChamber);
+
+        if is_starving || !is_in_chamber {
+            health.current -= QUEEN_SUFFER_DAMAGE * time.delta_seconds();
+        } else {
+            health.current =
+                (health.current + QUEEN_HEAL_PER_SECOND * time.delta_seconds()).min(health.max);
+        }
+    }
+}
+
+pub fn post_guard_jobs(
+    mut board: ResMut<JobBoard>,
+    queens: Query<(Entity, &Transform, &OnZLevel), With<Queen>>,
+) {
+    for (entity, queen_trans, queen_level) in queens.iter() {
+        let guards = board
+            .iter()
+            .filter(|job| job.kind == JobKind::Guard && job.target == Some(entity))
+            .count();
+
+        for _ in guards..QUEEN_GUARDS {
+            board.post(
+                JobKind::Guard,
+                GUARD_JOB_PRIORITY,
+                queen_level.0,
+                queen_trans.translation.xy(),
+                Some(entity),
+            );
+        }
+    }
+}
+
+// Queens die like any other fighter in `remove_dead`, this only tells when the last is gone
+pub fn check_queen_alive(
+    queens: Query<&Health, With<Queen>>,
+    mut removed: RemovedComponents<Queen>,
+    mut status: ResMut<ColonyStatus>,
+    mut lost: EventWriter<ColonyLost>,
+) {
+    // A world that never had a queen, like an empty one, has no colony to lose
+    let any_died = removed.read().count() > 0;
+    if *status == ColonyStatus::Lost || !any_died {
+        return;
+    }
+
+    if !queens.iter().any(|health| health.current > 0.0) {
+        *status = ColonyStatus::Lost;
+        lost.send(ColonyLost);
+    }
+}
+
+pub fn debug_queen(
+    queens: Query<(&Transform, &OnZLevel, &Health), With<Queen>>,
+    selected_z_level_q: Query<&SelectedZLevel>,
+    mut gizmos: Gizmos,
+) {
+    let selected_z_level = selected_z_level_q.single();
+
+    for (queen_trans, queen_level, health) in queens.iter() {
+        if queen_level.0 != selected_z_level.0 {
+            continue;
+        }
+
+        let position = queen_trans.translation.xy();
+        gizmos.circle_2d(position, 6.0, Color::PURPLE).segments(12);
+
+        // health bar
+        let bar_start = position + Vec2::new(-8.0, 9.0);
+        gizmos.line_2d(
+            bar_start,
+            bar_start + Vec2::X * 16.0 * (health.current / health.max).max(0.0),
+            Color::LIME_GREEN,
+        );
+    }
+}
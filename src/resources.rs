@@ -2,3 +2,33 @@ use bevy::prelude::*;
 
 #[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct FoodRes(u64);
+
+impl FoodRes {
+    pub fn new(amount: u64) -> Self {
+        Self(amount)
+    }
+
+    pub fn amount(&self) -> u64 {
+        self.0
+    }
+
+    pub fn add(&mut self, amount: u64) {
+        self.0 += amount;
+    }
+
+    // Takes `amount` out of the stock if there's enough of it
+    pub fn try_consume(&mut self, amount: u64) -> bool {
+        if self.0 < amount {
+            return false;
+        }
+        self.0 -= amount;
+        true
+    }
+}
+
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColonyStatus {
+    #[default]
+    Alive,
+    Lost,
+}
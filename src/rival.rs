@@ -0,0 +1,235 @@
+use bevy::prelude::*;
+use bevy_prng::ChaCha8Rng;
+use bevy_rand::prelude::*;
+use rand_core::RngCore;
+
+use crate::behavior::spawn_ant;
+use crate::components::*;
+use crate::designation::DesignationKind;
+use crate::economy::*;
+use crate::resources::FoodRes;
+use crate::world_map::*;
+
+/// A second, computer-run colony sharing the map. It has its own nest, food stock and
+/// pheromones, forages for the same food as the player and fights any ant that isn't its
+/// own. It has no queen, it turns food straight into new ants and digs a tunnel from its
+/// nest to right under the nearest food, where it opens an entrance to the surface.
+#[derive(Resource)]
+pub struct RivalColony {
+    pub z_level: i32,
+    pub nest: URect,           // in tiles
+    pub entrances: Vec<UVec2>, // tiles of its own, its ants keep away from the player's
+    pub food: FoodRes,
+    pub upkeep: FoodUpkeep,
+    pub tunnel_end: UVec2, // tile the tunnel it's digging has reached
+    pub dig_progress: f32,
+    pub time_until_decision: f32,
+}
+
+impl Default for RivalColony {
+    fn default() -> Self {
+        let nest = URect::from_corners(RIVAL_NEST_MIN, RIVAL_NEST_MIN + RIVAL_NEST_SIZE);
+        Self {
+            z_level: 0,
+            nest,
+            entrances: vec![RIVAL_NEST_ENTRANCE],
+            food: FoodRes::new(RIVAL_STARTING_FOOD),
+            upkeep: FoodUpkeep::default(),
+            tunnel_end: nest.center(),
+            dig_progress: 0.0,
+            time_until_decision: RIVAL_DECISION_INTERVAL,
+        }
+    }
+}
+
+impl RivalColony {
+    // Where its new ants come out
+    pub fn nest_center(&self) -> Vec2 {
+        self.nest.as_rect().center() * TILE_SIZE
+    }
+}
+
+const RIVAL_STARTING_ANTS: u32 = 60;
+const RIVAL_STARTING_FOOD: u64 = 50;
+const RIVAL_MAX_ANTS: usize = 400;
+// Food a new ant costs, and how much is always kept back to feed the ones it has
+const RIVAL_ANT_COST: u64 = 5;
+const RIVAL_FOOD_RESERVE: u64 = 10;
+// Seconds between looking at the stock and deciding whether to breed
+const RIVAL_DECISION_INTERVAL: f32 = 2.0;
+// Work its ants put into the tunnel per second, for each of them
+const RIVAL_DIG_RATE_PER_ANT: f32 = 0.02;
+// Castes it breeds and how often, out of the sum of the weights
+const RIVAL_CASTES: [(Caste, u32); 3] =
+    [(Caste::Forager, 6), (Caste::Worker, 3), (Caste::Soldier, 2)];
+
+fn random_rival_caste(rng: &mut GlobalEntropy<ChaCha8Rng>) -> Caste {
+    let total: u32 = RIVAL_CASTES.iter().map(|(_, weight)| weight).sum();
+    let mut roll = rng.next_u32() % total;
+    for (caste, weight) in RIVAL_CASTES {
+        if roll < weight {
+            return caste;
+        }
+        roll -= weight;
+    }
+    RIVAL_CASTES[0].0
+}
+
+pub fn spawn_rival_colony(
+    commands: &mut Commands,
+    rng: &mut GlobalEntropy<ChaCha8Rng>,
+    rival: &RivalColony,
+) {
+    for _ in 0..RIVAL_STARTING_ANTS {
+        let caste = random_rival_caste(rng);
+        spawn_ant(
+            commands,
+            rng,
+            rival.nest_center(),
+            rival.z_level,
+            caste,
+            Colony::RIVAL,
+        );
+    }
+}
+
+// Pays for the meals the rival's ants have eaten and breeds more with whatever is left
+// over
+pub fn rival_strategy(
+    mut commands: Commands,
+    mut rival: ResMut<RivalColony>,
+    mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
+    ants: Query<&Colony, With<Ant>>,
+    time: Res<Time>,
+) {
+    let rival_ants = ants
+        .iter()
+        .filter(|colony| **colony == Colony::RIVAL)
+        .count();
+
+    let rival = &mut *rival;
+    rival.upkeep.pay(&mut rival.food);
+
+    rival.time_until_decision -= time.delta_seconds();
+    if rival.time_until_decision > 0.0 {
+        return;
+    }
+    rival.time_until_decision = RIVAL_DECISION_INTERVAL;
+
+    if rival.upkeep.is_starving
+        || rival_ants >= RIVAL_MAX_ANTS
+        || rival.food.amount() < RIVAL_ANT_COST + RIVAL_FOOD_RESERVE
+    {
+        return;
+    }
+
+    if rival.food.try_consume(RIVAL_ANT_COST) {
+        let caste = random_rival_caste(&mut rng);
+        spawn_ant(
+            &mut commands,
+            &mut rng,
+            rival.nest_center(),
+            rival.z_level,
+            caste,
+            Colony::RIVAL,
+        );
+    }
+}
+
+// Digs the rival's tunnel one tile at a time toward the nearest food source up on the
+// surface, faster the more ants it has, and opens an entrance once it gets under it
+pub fn rival_dig_tunnels(
+    mut rival: ResMut<RivalColony>,
+    ants: Query<&Colony, With<Ant>>,
+    food: Query<&Transform, With<Food>>,
+    mut z_levels: Query<&mut ZLevel>,
+    mut tiles_changed: EventWriter<TilesChanged>,
+    time: Res<Time>,
+) {
+    let rival_ants = ants
+        .iter()
+        .filter(|colony| **colony == Colony::RIVAL)
+        .count();
+    rival.dig_progress += rival_ants as f32 * RIVAL_DIG_RATE_PER_ANT * time.delta_seconds();
+    if rival.dig_progress < DesignationKind::Excavate.work_required() {
+        return;
+    }
+
+    let tunnel_end = (rival.tunnel_end.as_vec2() + 0.5) * TILE_SIZE;
+    let Some(target) = food
+        .iter()
+        .map(|food_trans| food_trans.translation.xy())
+        .filter(|food_pos| {
+            food_pos.x >= 0.
+                && food_pos.y >= 0.
+                && two_d_index_to_one_d_index(world_pos_to_two_d_index(*food_pos)).is_some()
+        })
+        .min_by(|a, b| {
+            a.distance_squared(tunnel_end)
+                .total_cmp(&b.distance_squared(tunnel_end))
+        })
+        .map(world_pos_to_two_d_index)
+    else {
+        return;
+    };
+    let Some(mut z_level) = z_levels
+        .iter_mut()
+        .find(|z_level| z_level.z_level == rival.z_level)
+    else {
+        return;
+    };
+
+    // Walks along the tunnel already there and digs out the first tile of soil, only ever
+    // stepping straight so ants never have to squeeze past a corner
+    let mut tile = rival.tunnel_end;
+    let mut dug = false;
+    while tile != target {
+        let Some(next) = next_tunnel_tile(&z_level, tile, target) else {
+            break;
+        };
+
+        if z_level.building_at(next) == Some(BuildingType::None) {
+            // clay takes longer to get through
+            let factor = z_level[next].terrain.dig_work_factor().unwrap_or(1.0);
+            if rival.dig_progress >= DesignationKind::Excavate.work_required() * factor {
+                z_level[next].building = BuildingType::Tunnel;
+                tiles_changed.send(TilesChanged {
+                    z_level: rival.z_level,
+                    tiles: vec![next],
+                });
+                rival.dig_progress = 0.0;
+                dug = true;
+                tile = next;
+            }
+            break;
+        }
+        tile = next;
+    }
+    rival.tunnel_end = tile;
+
+    let needs_entrance = z_level.building_at(tile) != Some(BuildingType::Entrance);
+    if !dug && tile == target && needs_entrance && entrance_connects_to(rival.z_level).is_some() {
+        build_entrance(&mut z_levels, &mut tiles_changed, tile);
+        rival.entrances.push(tile);
+        rival.dig_progress = 0.0;
+    }
+}
+
+// Next tile toward `target`, along whichever axis it's further off on unless rock or water
+// is in the way. `None` if there's no way through either way.
+fn next_tunnel_tile(z_level: &ZLevel, tile: UVec2, target: UVec2) -> Option<UVec2> {
+    let to_target = target.as_ivec2() - tile.as_ivec2();
+    let along_x = IVec2::new(to_target.x.signum(), 0);
+    let along_y = IVec2::new(0, to_target.y.signum());
+    let steps = if to_target.x.abs() >= to_target.y.abs() {
+        [along_x, along_y]
+    } else {
+        [along_y, along_x]
+    };
+
+    steps
+        .into_iter()
+        .filter(|step| *step != IVec2::ZERO)
+        .map(|step| (tile.as_ivec2() + step).as_uvec2())
+        .find(|next| z_level.is_tile_walkable(*next) || z_level.is_tile_diggable(*next))
+}
@@ -1,9 +1,15 @@
 use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+
+use crate::behavior::interpolate_rendered_transforms;
+use crate::components::*;
+use crate::enemy::Bug;
 
 pub struct AnimationTestPlugin;
 
 impl Plugin for AnimationTestPlugin {
     fn build(&self, app: &mut App) {
+        add_sprite_sheets(app);
         app.add_systems(Startup, setup)
             .add_systems(Update, animate_sprite);
     }
@@ -20,11 +26,14 @@ struct AnimationTimer(Timer);
 
 fn animate_sprite(
     time: Res<Time>,
-    mut query: Query<(
-        &AnimationIndices,
-        &mut AnimationTimer,
-        &mut TextureAtlasSprite,
-    )>,
+    mut query: Query<
+        (
+            &AnimationIndices,
+            &mut AnimationTimer,
+            &mut TextureAtlasSprite,
+        ),
+        Without<Bug>,
+    >,
 ) {
     for (indices, mut timer, mut sprite) in &mut query {
         timer.tick(time.delta());
@@ -38,15 +47,95 @@ fn animate_sprite(
     }
 }
 
+// Frames along and down Ants.png and Bugs.png, how big they are comes from the image
+const SHEET_COLUMNS: usize = 12;
+const SHEET_ROWS: usize = 8;
+
+/// Loads and slices the sprite sheets. The frame size is only known once an image has
+/// loaded, until then its atlas handle points at nothing and draws nothing.
+struct SpriteSheetPlugin;
+
+impl Plugin for SpriteSheetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingSheets>()
+            .add_systems(Update, fill_sheet_atlases);
+    }
+}
+
+// Sheets still loading, with the atlas each one is cut into
+#[derive(Resource, Default)]
+struct PendingSheets(Vec<(Handle<Image>, Handle<TextureAtlas>)>);
+
+fn add_sprite_sheets(app: &mut App) {
+    if !app.is_plugin_added::<SpriteSheetPlugin>() {
+        app.add_plugins(SpriteSheetPlugin);
+    }
+}
+
+fn sheet_atlas(
+    path: &'static str,
+    asset_server: &AssetServer,
+    texture_atlases: &Assets<TextureAtlas>,
+    pending: &mut PendingSheets,
+) -> Handle<TextureAtlas> {
+    let atlas = texture_atlases.reserve_handle();
+    pending.0.push((asset_server.load(path), atlas.clone()));
+    atlas
+}
+
+fn fill_sheet_atlases(
+    mut pending: ResMut<PendingSheets>,
+    images: Res<Assets<Image>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    pending.0.retain(|(image_handle, atlas)| {
+        let Some(image) = images.get(image_handle) else {
+            return true;
+        };
+
+        let size = image.texture_descriptor.size;
+        let frame_size = Vec2::new(
+            size.width as f32 / SHEET_COLUMNS as f32,
+            size.height as f32 / SHEET_ROWS as f32,
+        );
+        texture_atlases.insert(
+            atlas,
+            TextureAtlas::from_grid(
+                image_handle.clone(),
+                frame_size,
+                SHEET_COLUMNS,
+                SHEET_ROWS,
+                None,
+                None,
+            ),
+        );
+        false
+    });
+}
+
+fn ant_texture_atlas(
+    asset_server: &AssetServer,
+    texture_atlases: &Assets<TextureAtlas>,
+    pending: &mut PendingSheets,
+) -> Handle<TextureAtlas> {
+    sheet_atlas("Ants.png", asset_server, texture_atlases, pending)
+}
+
+fn bug_texture_atlas(
+    asset_server: &AssetServer,
+    texture_atlases: &Assets<TextureAtlas>,
+    pending: &mut PendingSheets,
+) -> Handle<TextureAtlas> {
+    sheet_atlas("Bugs.png", asset_server, texture_atlases, pending)
+}
+
 fn setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+    mut pending: ResMut<PendingSheets>,
 ) {
-    let texture_handle = asset_server.load("Ants.png");
-    let texture_atlas =
-        TextureAtlas::from_grid(texture_handle, Vec2::new(30.0, 30.0), 12, 8, None, None);
-    let texture_atlas_handle = texture_atlases.add(texture_atlas);
+    let texture_atlas_handle = ant_texture_atlas(&asset_server, &texture_atlases, &mut pending);
     // Use only the subset of sprites in the sheet that make up the run animation
     let animation_indices = AnimationIndices { first: 1, last: 3 };
     commands.spawn((
@@ -60,3 +149,157 @@ fn setup(
         AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
     ));
 }
+
+/// Draws every `Ant` with its caste's frame from Ants.png.
+pub struct AntSpritePlugin;
+
+impl Plugin for AntSpritePlugin {
+    fn build(&self, app: &mut App) {
+        add_sprite_sheets(app);
+        app.add_systems(Startup, setup_ant_atlas)
+            .add_systems(Update, add_ant_sprites)
+            .add_systems(
+                PostUpdate,
+                orient_ant_sprites
+                    .after(TransformSystem::TransformPropagate)
+                    .after(interpolate_rendered_transforms),
+            );
+    }
+}
+
+const ANT_SPRITE_SIZE: f32 = 8.0;
+const ANT_SPRITE_Z: f32 = 2.0;
+// Rival ants are drawn tinted so they can be told apart from the player's
+const RIVAL_ANT_TINT: Color = Color::rgb(1.0, 0.45, 0.45);
+
+#[derive(Resource)]
+struct AntAtlas(Handle<TextureAtlas>);
+
+fn setup_ant_atlas(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+    mut pending: ResMut<PendingSheets>,
+) {
+    commands.insert_resource(AntAtlas(ant_texture_atlas(
+        &asset_server,
+        &texture_atlases,
+        &mut pending,
+    )));
+}
+
+fn add_ant_sprites(
+    mut commands: Commands,
+    ants: Query<(Entity, &Ant, &Colony), Added<Ant>>,
+    ant_atlas: Res<AntAtlas>,
+) {
+    for (entity, ant, colony) in ants.iter() {
+        let mut sprite = TextureAtlasSprite::new(ant.caste.atlas_index());
+        sprite.custom_size = Some(Vec2::splat(ANT_SPRITE_SIZE));
+        if *colony != Colony::PLAYER {
+            sprite.color = RIVAL_ANT_TINT;
+        }
+
+        commands.entity(entity).insert((
+            ant_atlas.0.clone(),
+            sprite,
+            GlobalTransform::default(),
+            VisibilityBundle::default(),
+        ));
+    }
+}
+
+// The simulation turns ants with `looking_at`, which tips them out of the xy plane, so the
+// drawn sprite is instead turned flat to face along the ant's heading
+fn orient_ant_sprites(mut ants: Query<(&Transform, &mut GlobalTransform), With<Ant>>) {
+    for (ant_trans, mut global_trans) in ants.iter_mut() {
+        let heading = ant_trans.forward().xy();
+        let (_, _, translation) = global_trans.to_scale_rotation_translation();
+
+        *global_trans = GlobalTransform::from(
+            Transform::from_translation(translation.truncate().extend(ANT_SPRITE_Z)).with_rotation(
+                Quat::from_rotation_z(
+                    f32::atan2(heading.y, heading.x) - std::f32::consts::FRAC_PI_2,
+                ),
+            ),
+        );
+    }
+}
+
+/// Draws every `Bug` with its kind's walk cycle from Bugs.png.
+pub struct BugSpritePlugin;
+
+impl Plugin for BugSpritePlugin {
+    fn build(&self, app: &mut App) {
+        add_sprite_sheets(app);
+        app.add_systems(Startup, setup_bug_atlas)
+            .add_systems(Update, (add_bug_sprites, animate_bug_sprites));
+    }
+}
+
+const BUG_SPRITE_SIZE: f32 = 12.0;
+const BUG_WALK_FRAMES: usize = 3;
+// Seconds per frame for a bug moving at `BUG_REFERENCE_SPEED`, faster bugs step quicker
+const BUG_FRAME_TIME: f32 = 0.1;
+const BUG_REFERENCE_SPEED: f32 = 30.0;
+
+#[derive(Resource)]
+struct BugAtlas(Handle<TextureAtlas>);
+
+fn setup_bug_atlas(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+    mut pending: ResMut<PendingSheets>,
+) {
+    commands.insert_resource(BugAtlas(bug_texture_atlas(
+        &asset_server,
+        &texture_atlases,
+        &mut pending,
+    )));
+}
+
+fn add_bug_sprites(
+    mut commands: Commands,
+    bugs: Query<(Entity, &Bug), Added<Bug>>,
+    bug_atlas: Res<BugAtlas>,
+) {
+    for (entity, bug) in bugs.iter() {
+        let first = bug.kind.atlas_index();
+        let mut sprite = TextureAtlasSprite::new(first);
+        sprite.custom_size = Some(Vec2::splat(BUG_SPRITE_SIZE));
+
+        commands.entity(entity).insert((
+            bug_atlas.0.clone(),
+            sprite,
+            AnimationIndices {
+                first,
+                last: first + BUG_WALK_FRAMES - 1,
+            },
+            AnimationTimer(Timer::from_seconds(BUG_FRAME_TIME, TimerMode::Repeating)),
+            GlobalTransform::default(),
+            VisibilityBundle::default(),
+        ));
+    }
+}
+
+fn animate_bug_sprites(
+    time: Res<Time>,
+    mut bugs: Query<(
+        &Bug,
+        &AnimationIndices,
+        &mut AnimationTimer,
+        &mut TextureAtlasSprite,
+    )>,
+) {
+    for (bug, indices, mut timer, mut sprite) in bugs.iter_mut() {
+        timer.tick(time.delta().mul_f32(bug.speed / BUG_REFERENCE_SPEED));
+        if timer.just_finished() {
+            sprite.index = if sprite.index == indices.last {
+                indices.first
+            } else {
+                sprite.index + 1
+            };
+        }
+    }
+}
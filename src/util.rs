@@ -6,3 +6,10 @@ use crate::*;
 pub fn rand_uniform_f32(rng: &mut EntropyComponent<ChaCha8Rng>) -> f32 {
     ((rng.next_u64() & 0xffffffff) as f64 / (0x7fffffff as f64) - 1.0) as f32
 }
+
+// Value following `flag` on the command line, if present and parseable
+pub fn arg_value<T: std::str::FromStr>(flag: &str) -> Option<T> {
+    let mut args = std::env::args().skip_while(|arg| arg != flag);
+    args.next()?;
+    args.next()?.parse().ok()
+}
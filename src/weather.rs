@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+use bevy_prng::ChaCha8Rng;
+use bevy_rand::prelude::*;
+use rand_core::RngCore;
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum WeatherKind {
+    Clear,
+    Rain,
+}
+
+/// Weather over the surface. Rain slows down ants caught out in it and washes their
+/// trails away, the nest underground doesn't notice either.
+#[derive(Resource, Debug)]
+pub struct Weather {
+    pub kind: WeatherKind,
+    pub time_until_change: f32,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Self {
+            kind: WeatherKind::Clear,
+            time_until_change: CLEAR_DURATION.0,
+        }
+    }
+}
+
+impl Weather {
+    pub fn surface_speed_factor(&self) -> f32 {
+        match self.kind {
+            WeatherKind::Clear => 1.0,
+            WeatherKind::Rain => 0.6,
+        }
+    }
+
+    // Times faster pheromones evaporate on the surface
+    pub fn surface_evaporation_factor(&self) -> f32 {
+        match self.kind {
+            WeatherKind::Clear => 1.0,
+            WeatherKind::Rain => 4.0,
+        }
+    }
+}
+
+// Shortest and longest spell of each kind of weather, in seconds
+const CLEAR_DURATION: (f32, f32) = (120.0, 300.0);
+const RAIN_DURATION: (f32, f32) = (20.0, 60.0);
+
+pub fn update_weather(
+    mut weather: ResMut<Weather>,
+    mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
+    time: Res<Time>,
+) {
+    weather.time_until_change -= time.delta_seconds();
+    if weather.time_until_change > 0.0 {
+        return;
+    }
+
+    let (kind, (shortest, longest)) = match weather.kind {
+        WeatherKind::Clear => (WeatherKind::Rain, RAIN_DURATION),
+        WeatherKind::Rain => (WeatherKind::Clear, CLEAR_DURATION),
+    };
+    let roll = rng.next_u32() as f32 / u32::MAX as f32;
+    weather.kind = kind;
+    weather.time_until_change = shortest + roll * (longest - shortest);
+}
@@ -1,10 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::ops::{Index, IndexMut};
 use strum_macros::EnumIter;
 
 use bevy::{math::Vec4, render::render_resource::FilterMode};
 
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 
 use bevy_entitiles::{
@@ -17,6 +18,11 @@ use bevy_entitiles::{
     EntiTilesPlugin,
 };
 
+use crate::components::OnZLevel;
+use crate::designation::*;
+use crate::pheromone::PheromoneKind;
+use crate::worldgen::generate_world;
+
 pub const TILE_SIZE: Vec2 = Vec2::new(16., 16.);
 pub const MAP_SIZE: UVec2 = UVec2::new(50, 50);
 pub const MAP_DATA_SIZE: usize = (MAP_SIZE.x * MAP_SIZE.y) as usize;
@@ -37,6 +43,24 @@ pub fn world_map_center_3d() -> Vec3 {
 const HOVER_COLOR: Vec4 = Vec4::new(0., 0., 0., 0.1);
 const NORMAL_COLOR: Vec4 = Vec4::new(1., 1., 1., 1.);
 const NORMAL_TILE_INDEX: u32 = 0;
+const STARTING_TUNNEL_SIZE: UVec2 = UVec2::new(5, 5);
+pub const STARTING_NURSERY_MIN: UVec2 = UVec2::new(MAP_SIZE.x / 2 + 1, MAP_SIZE.y / 2 + 1);
+pub const STARTING_NURSERY_SIZE: UVec2 = UVec2::new(2, 2);
+pub const STARTING_QUEEN_CHAMBER_MIN: UVec2 = UVec2::new(MAP_SIZE.x / 2 - 3, MAP_SIZE.y / 2 - 3);
+pub const STARTING_QUEEN_CHAMBER_SIZE: UVec2 = UVec2::new(2, 2);
+pub const STARTING_FOOD_STORAGE_MIN: UVec2 = UVec2::new(MAP_SIZE.x / 2 + 1, MAP_SIZE.y / 2 - 3);
+pub const STARTING_FOOD_STORAGE_SIZE: UVec2 = UVec2::new(2, 2);
+// The rival colony's nest, tucked in a corner away from the player's
+pub const RIVAL_NEST_MIN: UVec2 = UVec2::new(4, 4);
+pub const RIVAL_NEST_SIZE: UVec2 = UVec2::new(4, 4);
+// Open ground above the nests, where food, bugs and weather are
+pub const SURFACE_Z_LEVEL: i32 = 1;
+// Deepest level the world is generated down to
+pub const LOWEST_Z_LEVEL: i32 = -3;
+// Holes up to the surface each colony starts with
+pub const STARTING_ENTRANCE: UVec2 = UVec2::new(MAP_SIZE.x / 2 - 4, MAP_SIZE.y / 2 + 3);
+pub const RIVAL_NEST_ENTRANCE: UVec2 =
+    UVec2::new(RIVAL_NEST_MIN.x, RIVAL_NEST_MIN.y + RIVAL_NEST_SIZE.y - 1);
 
 #[derive(Component)]
 pub struct HoveredTile;
@@ -47,8 +71,24 @@ pub enum BuildingType {
     Tunnel,
     QueenChamber,
     FoodStorage,
+    Nursery,
+    Shaft,    // connects to a shaft on the same tile one level up or down
+    Ground,   // open ground on the surface
+    Entrance, // connects the surface with the level right below it on the same tile
 }
 
+// What the player can designate, in the order the palette shows them. `None` fills a
+// tile back in.
+pub const PLAYER_BUILDINGS: [BuildingType; 7] = [
+    BuildingType::None,
+    BuildingType::Tunnel,
+    BuildingType::QueenChamber,
+    BuildingType::FoodStorage,
+    BuildingType::Nursery,
+    BuildingType::Shaft,
+    BuildingType::Entrance,
+];
+
 impl fmt::Display for BuildingType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -56,10 +96,112 @@ impl fmt::Display for BuildingType {
             BuildingType::Tunnel => write!(f, "Tunnel"),
             BuildingType::QueenChamber => write!(f, "Queen"),
             BuildingType::FoodStorage => write!(f, "Food"),
+            BuildingType::Nursery => write!(f, "Nursery"),
+            BuildingType::Shaft => write!(f, "Shaft"),
+            BuildingType::Ground => write!(f, "Ground"),
+            BuildingType::Entrance => write!(f, "Entrance"),
+        }
+    }
+}
+
+impl BuildingType {
+    // Pheromone the building gives off on its own, if any
+    pub fn emitted_pheromone(&self) -> Option<PheromoneKind> {
+        match self {
+            BuildingType::QueenChamber => Some(PheromoneKind::QueenPresence),
+            _ => None,
+        }
+    }
+
+    // Whether eggs, larvae and pupae can be kept here
+    pub fn holds_brood(&self) -> bool {
+        matches!(self, BuildingType::QueenChamber | BuildingType::Nursery)
+    }
+
+    // Whether the player can designate this on `z_level`. Nothing is dug into or filled
+    // on the surface, it only ever gets entrances.
+    pub fn is_placeable_on(&self, z_level: i32) -> bool {
+        match self {
+            BuildingType::Ground => false,
+            BuildingType::Entrance => entrance_connects_to(z_level).is_some(),
+            _ => z_level < SURFACE_Z_LEVEL,
+        }
+    }
+
+    // Whether tired ants can rest here, they sleep among the brood
+    pub fn is_chamber(&self) -> bool {
+        self.holds_brood()
+    }
+}
+
+// The other level an entrance on `z_level` leads to, if one can be built there at all
+pub fn entrance_connects_to(z_level: i32) -> Option<i32> {
+    if z_level == SURFACE_Z_LEVEL {
+        Some(SURFACE_Z_LEVEL - 1)
+    } else if z_level == SURFACE_Z_LEVEL - 1 {
+        Some(SURFACE_Z_LEVEL)
+    } else {
+        None
+    }
+}
+
+// Opens an entrance on `tile` on both the surface and the level below it
+pub fn build_entrance(
+    z_levels: &mut Query<&mut ZLevel>,
+    tiles_changed: &mut EventWriter<TilesChanged>,
+    tile: UVec2,
+) {
+    for mut z_level in z_levels.iter_mut() {
+        if entrance_connects_to(z_level.z_level).is_none() || z_level.building_at(tile).is_none() {
+            continue;
         }
+        z_level[tile].building = BuildingType::Entrance;
+        tiles_changed.send(TilesChanged {
+            z_level: z_level.z_level,
+            tiles: vec![tile],
+        });
     }
 }
 
+/// What an undug tile is made of. Only matters until something is dug out of it.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Terrain {
+    Loam, // loose topsoil
+    Soil,
+    Clay,
+    Rock,
+    Water,
+}
+
+impl Terrain {
+    // How many times the usual work digging through it takes, `None` if it can't be dug
+    pub fn dig_work_factor(&self) -> Option<f32> {
+        match self {
+            Terrain::Loam => Some(0.7),
+            Terrain::Soil => Some(1.0),
+            Terrain::Clay => Some(2.5),
+            Terrain::Rock | Terrain::Water => None,
+        }
+    }
+
+    pub fn color(&self) -> Vec4 {
+        match self {
+            Terrain::Loam => Vec4::new(1.0, 0.9, 0.75, 1.0),
+            Terrain::Soil => NORMAL_COLOR,
+            Terrain::Clay => Vec4::new(0.9, 0.55, 0.4, 1.0),
+            Terrain::Rock => Vec4::new(0.45, 0.45, 0.5, 1.0),
+            Terrain::Water => Vec4::new(0.35, 0.55, 1.0, 1.0),
+        }
+    }
+}
+
+/// Sent whenever the building on any tile of a `ZLevel` changes.
+#[derive(Event)]
+pub struct TilesChanged {
+    pub z_level: i32,
+    pub tiles: Vec<UVec2>,
+}
+
 #[derive(Component)]
 pub struct SelectedZLevel(pub i32);
 
@@ -92,7 +234,7 @@ impl IndexMut<UVec2> for ZLevel {
 }
 
 impl ZLevel {
-    fn with_level(level: i32) -> ZLevel {
+    pub fn with_level(level: i32) -> ZLevel {
         let default_tile = TileState::default();
         let mut tiles = vec![];
         for _ in 0..MAP_DATA_SIZE {
@@ -116,27 +258,90 @@ impl ZLevel {
 
     pub fn is_tile_walkable(&self, pos: UVec2) -> bool {
         match two_d_index_to_one_d_index(pos) {
-            Some(i) => return self.tiles[i].building == BuildingType::Tunnel,
+            // every building is dug out of the soil, so any of them can be walked through
+            Some(i) => return self.tiles[i].building != BuildingType::None,
             None => return false,
         }
     }
+
+    // Walkability of every tile, indexed like `tiles`
+    pub fn walkable_tiles(&self) -> Vec<bool> {
+        (0..self.tiles.len())
+            .map(|i| self.is_tile_walkable(one_d_index_to_two_d_index(i)))
+            .collect()
+    }
+
+    pub fn building_at(&self, pos: UVec2) -> Option<BuildingType> {
+        two_d_index_to_one_d_index(pos).map(|i| self.tiles[i].building)
+    }
+
+    // Whether the tile is still solid and soft enough to dig through
+    pub fn is_tile_diggable(&self, pos: UVec2) -> bool {
+        two_d_index_to_one_d_index(pos).map_or(false, |i| {
+            self.tiles[i].building == BuildingType::None
+                && self.tiles[i].terrain.dig_work_factor().is_some()
+        })
+    }
+
+    pub fn building_at_world_pos(&self, world_pos: Vec2) -> Option<BuildingType> {
+        if world_pos.x < 0. || world_pos.y < 0. {
+            return None;
+        }
+        self.building_at(world_pos_to_two_d_index(world_pos))
+    }
+}
+
+/// Read-only access to the map state for systems outside this module.
+#[derive(SystemParam)]
+pub struct WorldMap<'w, 's> {
+    z_levels: Query<'w, 's, &'static ZLevel>,
+}
+
+impl<'w, 's> WorldMap<'w, 's> {
+    pub fn z_level(&self, level: i32) -> Option<&ZLevel> {
+        self.z_levels
+            .iter()
+            .find(|z_level| z_level.z_level == level)
+    }
+
+    pub fn building_at(&self, level: i32, pos: UVec2) -> Option<BuildingType> {
+        self.z_level(level)?.building_at(pos)
+    }
+
+    pub fn is_tile_walkable(&self, level: i32, pos: UVec2) -> bool {
+        self.z_level(level)
+            .map_or(false, |z_level| z_level.is_tile_walkable(pos))
+    }
 }
 
 #[derive(Clone)]
 pub struct TileState {
     pub building: BuildingType,
-    pub pher_refs: Vec<Entity>,
+    pub terrain: Terrain,
 }
 
 impl Default for TileState {
     fn default() -> Self {
         Self {
             building: BuildingType::None,
-            pher_refs: vec![],
+            terrain: Terrain::Soil,
         }
     }
 }
 
+impl TileState {
+    // Undug tiles are tinted by what they're made of
+    fn tile_builder(&self, building_tile_map: &BuildingTypeToTileIndexMap) -> TileBuilder {
+        let tile = building_tile_map.0.get(&self.building).cloned().unwrap();
+        let color = if self.building == BuildingType::None {
+            self.terrain.color()
+        } else {
+            NORMAL_COLOR
+        };
+        TileBuilder::new(tile).with_color(color)
+    }
+}
+
 #[derive(Component)]
 pub struct MapPos(pub UVec2);
 
@@ -156,6 +361,21 @@ impl Default for CursorPos {
     }
 }
 
+/// Generates the `ZLevel` map data only. Needs no window, renderer or tilemap, so the
+/// simulation can use it on its own.
+pub struct ZLevelPlugin;
+
+impl Plugin for ZLevelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TilesChanged>()
+            // generated ahead of `Startup` so the levels are there for everything that
+            // fills them or draws them
+            .add_systems(PreStartup, generate_world);
+    }
+}
+
+/// Tilemap rendering and mouse/keyboard building on top of the `ZLevel` data added by
+/// `ZLevelPlugin`.
 pub struct WorldMapPlugin;
 
 impl Plugin for WorldMapPlugin {
@@ -172,17 +392,30 @@ impl Plugin for WorldMapPlugin {
                     change_selected_z_level,
                 ),
             )
-            .add_systems(Update, (mouse_building, mouse_hover));
+            .add_systems(
+                Update,
+                (
+                    mouse_building,
+                    mouse_hover,
+                    show_designations.after(mouse_hover),
+                    redraw_changed_tiles.before(show_designations),
+                    show_selected_z_level_only,
+                ),
+            );
     }
 }
 
-fn setup(mut commands: Commands, assets_server: Res<AssetServer>) {
-    commands.spawn(BuildingTypeToTileIndexMap(HashMap::from([
+fn setup(mut commands: Commands, assets_server: Res<AssetServer>, z_level_q: Query<&ZLevel>) {
+    let building_tile_map = BuildingTypeToTileIndexMap(HashMap::from([
         (BuildingType::None, 0),
         (BuildingType::Tunnel, 1),
         (BuildingType::QueenChamber, 3),
         (BuildingType::FoodStorage, 2),
-    ])));
+        (BuildingType::Nursery, 3),
+        (BuildingType::Shaft, 1),
+        (BuildingType::Ground, 1),
+        (BuildingType::Entrance, 3),
+    ]));
 
     let (_, mut tilemap) = TilemapBuilder::new(TileType::Square, MAP_SIZE, TILE_SIZE)
         .with_texture(
@@ -196,45 +429,90 @@ fn setup(mut commands: Commands, assets_server: Res<AssetServer>) {
         .with_translation(Vec2 { x: 8., y: 0. })
         .build(&mut commands);
 
-    tilemap.fill_rect(
-        &mut commands,
-        FillArea::full(&tilemap),
-        &TileBuilder::new(NORMAL_TILE_INDEX),
-    );
-
     commands.spawn(SelectedBuilding {
         selected_type: BuildingType::Tunnel,
     });
 
-    commands.spawn(SelectedZLevel(0));
-
-    let mut tiles = Vec::new();
-    tiles.resize(
-        MAP_SIZE.x as usize * MAP_SIZE.y as usize,
-        TileState::default(),
-    );
+    let selected_z_level = 0;
+    if let Some(z_level) = z_level_q
+        .iter()
+        .find(|z_level| z_level.z_level == selected_z_level)
+    {
+        draw_z_level(&mut commands, &mut tilemap, z_level, &building_tile_map);
+    }
 
-    let mut z_level = ZLevel::with_level(0);
-    let starting_tunnel_size = UVec2::new(5, 5);
+    commands.spawn(SelectedZLevel(selected_z_level));
+    commands.spawn(building_tile_map);
+}
 
+// Starting rooms of both colonies, dug out of the top level underground
+pub fn carve_starting_nests(z_level: &mut ZLevel) {
     z_level.set_area(
         URect::from_corners(
-            MAP_SIZE / 2 - starting_tunnel_size,
-            MAP_SIZE / 2 + starting_tunnel_size,
+            MAP_SIZE / 2 - STARTING_TUNNEL_SIZE,
+            MAP_SIZE / 2 + STARTING_TUNNEL_SIZE,
         ),
         BuildingType::Tunnel,
     );
-    commands.spawn(z_level);
-
-    tilemap.fill_rect(
-        &mut commands,
-        FillArea::new(
-            MAP_SIZE / 2 - starting_tunnel_size,
-            Some(starting_tunnel_size * 2),
-            &tilemap,
+    z_level.set_area(
+        URect::from_corners(
+            STARTING_NURSERY_MIN,
+            STARTING_NURSERY_MIN + STARTING_NURSERY_SIZE,
+        ),
+        BuildingType::Nursery,
+    );
+    z_level.set_area(
+        URect::from_corners(
+            STARTING_QUEEN_CHAMBER_MIN,
+            STARTING_QUEEN_CHAMBER_MIN + STARTING_QUEEN_CHAMBER_SIZE,
+        ),
+        BuildingType::QueenChamber,
+    );
+    z_level.set_area(
+        URect::from_corners(
+            STARTING_FOOD_STORAGE_MIN,
+            STARTING_FOOD_STORAGE_MIN + STARTING_FOOD_STORAGE_SIZE,
         ),
-        &TileBuilder::new(1),
+        BuildingType::FoodStorage,
     );
+    z_level.set_area(
+        URect::from_corners(RIVAL_NEST_MIN, RIVAL_NEST_MIN + RIVAL_NEST_SIZE),
+        BuildingType::Tunnel,
+    );
+}
+
+// Everything the starting nests take up, with room to grow around them
+pub fn starting_nest_areas(margin: u32) -> [URect; 2] {
+    let player = URect::from_corners(
+        MAP_SIZE / 2 - STARTING_TUNNEL_SIZE,
+        MAP_SIZE / 2 + STARTING_TUNNEL_SIZE,
+    );
+    let rival = URect::from_corners(RIVAL_NEST_MIN, RIVAL_NEST_MIN + RIVAL_NEST_SIZE);
+    [player, rival].map(|area| {
+        URect::from_corners(
+            UVec2::new(
+                area.min.x.saturating_sub(margin),
+                area.min.y.saturating_sub(margin),
+            ),
+            (area.max + margin).min(MAP_SIZE),
+        )
+    })
+}
+
+// Draws every tile of `z_level` onto the tilemap
+fn draw_z_level(
+    commands: &mut Commands,
+    tilemap: &mut Tilemap,
+    z_level: &ZLevel,
+    building_tile_map: &BuildingTypeToTileIndexMap,
+) {
+    for (i_tile, tile_state) in z_level.tiles.iter().enumerate() {
+        tilemap.set(
+            commands,
+            one_d_index_to_two_d_index(i_tile),
+            &tile_state.tile_builder(building_tile_map),
+        );
+    }
 }
 
 pub fn get_local_neighborhood(world_pos: Vec2) -> Vec<UVec2> {
@@ -302,12 +580,10 @@ fn reset_hovered_tiles(
 
         for (entity, hovered_tile_pos) in hovered_tiles_q.iter() {
             if let Some(tilemap_index) = two_d_index_to_one_d_index(hovered_tile_pos.0.xy()) {
-                let building = z_level.tiles[tilemap_index].building;
-                let tile = building_tile_map.0.get(&building).unwrap();
                 tilemap.set(
                     &mut commands,
                     hovered_tile_pos.0.xy(),
-                    &TileBuilder::new(*tile).with_color(NORMAL_COLOR),
+                    &z_level.tiles[tilemap_index].tile_builder(building_tile_map),
                 );
 
                 commands.entity(entity).despawn();
@@ -338,46 +614,144 @@ fn mouse_hover(
     commands.spawn((HoveredTile, MapPos(cursor_map_pos)));
 }
 
+// Left click designates the hovered tile for the selected building, right click cancels
+// whatever is designated there. The tile only changes once ants have done the work.
 fn mouse_building(
-    mut commands: Commands,
     buttons: Res<Input<MouseButton>>,
     cursor_pos: Res<CursorPos>,
-    mut tilemap_q: Query<&mut Tilemap>,
     selected_building_q: Query<&mut SelectedBuilding>,
     current_z_level_q: Query<&SelectedZLevel>,
-    building_type_tile_index_map_q: Query<&BuildingTypeToTileIndexMap>,
-    mut z_level_q: Query<&mut ZLevel>,
+    mut designate: EventWriter<Designate>,
+    mut cancel: EventWriter<CancelDesignation>,
 ) {
-    if !buttons.pressed(MouseButton::Left) {
+    if cursor_pos.0.x < 0. || cursor_pos.0.y < 0. {
         return;
     }
 
     let selected_z_level = current_z_level_q.single();
     let cursor_map_pos = world_pos_to_two_d_index(cursor_pos.0);
+    if two_d_index_to_one_d_index(cursor_map_pos).is_none() {
+        return;
+    }
 
-    let selected_building: &SelectedBuilding = selected_building_q.single();
-    let building_tile_map = building_type_tile_index_map_q.single();
+    if buttons.pressed(MouseButton::Left) {
+        let selected_building: &SelectedBuilding = selected_building_q.single();
+        designate.send(Designate {
+            z_level: selected_z_level.0,
+            tile: cursor_map_pos,
+            building: selected_building.selected_type,
+        });
+    } else if buttons.pressed(MouseButton::Right) {
+        cancel.send(CancelDesignation {
+            z_level: selected_z_level.0,
+            tile: cursor_map_pos,
+        });
+    }
+}
 
-    let tile = building_tile_map
-        .0
-        .get(&selected_building.selected_type)
-        .unwrap();
+const DESIGNATION_MIN_ALPHA: f32 = 0.2;
+const DESIGNATION_MAX_ALPHA: f32 = 0.7;
 
+// Draws designated tiles as a faint version of what they're becoming, filling in as work
+// progresses, and puts tiles back once their designation is gone
+fn show_designations(
+    mut commands: Commands,
+    mut tilemap_q: Query<&mut Tilemap>,
+    designation_q: Query<&Designation>,
+    selected_z_level_q: Query<&SelectedZLevel>,
+    z_level_q: Query<&ZLevel>,
+    building_type_tile_index_map_q: Query<&BuildingTypeToTileIndexMap>,
+    mut drawn: Local<HashSet<UVec2>>,
+) {
     let mut tilemap = tilemap_q.single_mut();
+    let selected_z_level = selected_z_level_q.single();
+    let building_tile_map = building_type_tile_index_map_q.single();
 
-    tilemap.set(
-        &mut commands,
-        cursor_map_pos.xy(),
-        &TileBuilder::new(*tile).with_color(NORMAL_COLOR),
-    );
+    let mut still_designated = HashSet::new();
+    for designation in designation_q
+        .iter()
+        .filter(|designation| designation.z_level == selected_z_level.0)
+    {
+        let tile = building_tile_map.0.get(&designation.building).unwrap();
+        let alpha = DESIGNATION_MIN_ALPHA
+            + (DESIGNATION_MAX_ALPHA - DESIGNATION_MIN_ALPHA) * designation.progress.min(1.0);
+        tilemap.set(
+            &mut commands,
+            designation.tile,
+            &TileBuilder::new(*tile).with_color(Vec4::new(1., 1., 1., alpha)),
+        );
+        still_designated.insert(designation.tile);
+    }
 
-    for mut z_level in z_level_q.iter_mut() {
-        if z_level.z_level != selected_z_level.0 {
-            continue;
+    if let Some(z_level) = z_level_q
+        .iter()
+        .find(|z_level| z_level.z_level == selected_z_level.0)
+    {
+        for tile_pos in drawn.difference(&still_designated) {
+            if let Some(i) = two_d_index_to_one_d_index(*tile_pos) {
+                tilemap.set(
+                    &mut commands,
+                    *tile_pos,
+                    &z_level.tiles[i].tile_builder(building_tile_map),
+                );
+            }
         }
+    }
+
+    *drawn = still_designated;
+}
 
-        if let Some(index) = two_d_index_to_one_d_index(cursor_map_pos.xy()) {
-            z_level.tiles[index].building = selected_building.selected_type;
+// Puts tiles the simulation changed on its own, like the rival colony's tunnels, on the
+// tilemap
+fn redraw_changed_tiles(
+    mut commands: Commands,
+    mut tilemap_q: Query<&mut Tilemap>,
+    mut tiles_changed: EventReader<TilesChanged>,
+    selected_z_level_q: Query<&SelectedZLevel>,
+    z_level_q: Query<&ZLevel>,
+    building_type_tile_index_map_q: Query<&BuildingTypeToTileIndexMap>,
+) {
+    let mut tilemap = tilemap_q.single_mut();
+    let selected_z_level = selected_z_level_q.single();
+    let building_tile_map = building_type_tile_index_map_q.single();
+    let Some(z_level) = z_level_q
+        .iter()
+        .find(|z_level| z_level.z_level == selected_z_level.0)
+    else {
+        return;
+    };
+
+    for changed in tiles_changed
+        .read()
+        .filter(|changed| changed.z_level == selected_z_level.0)
+    {
+        for tile_pos in changed.tiles.iter() {
+            if let Some(i) = two_d_index_to_one_d_index(*tile_pos) {
+                tilemap.set(
+                    &mut commands,
+                    *tile_pos,
+                    &z_level.tiles[i].tile_builder(building_tile_map),
+                );
+            }
+        }
+    }
+}
+
+// Hides everything that isn't on the level being looked at
+fn show_selected_z_level_only(
+    selected_z_level_q: Query<&SelectedZLevel>,
+    mut visibility_q: Query<(&OnZLevel, &mut Visibility)>,
+) {
+    let selected_z_level = selected_z_level_q.single();
+
+    for (on_z_level, mut visibility) in visibility_q.iter_mut() {
+        let wanted = if on_z_level.0 == selected_z_level.0 {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != wanted {
+            *visibility = wanted;
         }
     }
 }
@@ -393,14 +767,20 @@ fn change_selected_building_type(
         selected_building.selected_type = BuildingType::QueenChamber;
     } else if keyboard_input.pressed(KeyCode::Key3) {
         selected_building.selected_type = BuildingType::FoodStorage;
+    } else if keyboard_input.pressed(KeyCode::Key4) {
+        selected_building.selected_type = BuildingType::Nursery;
+    } else if keyboard_input.pressed(KeyCode::Key5) {
+        selected_building.selected_type = BuildingType::Shaft;
+    } else if keyboard_input.pressed(KeyCode::Key6) {
+        selected_building.selected_type = BuildingType::Entrance;
     }
 }
 
-fn one_d_index_to_two_d_index(index: usize) -> UVec2 {
+pub fn one_d_index_to_two_d_index(index: usize) -> UVec2 {
     return UVec2::new(index as u32 % MAP_SIZE.x, index as u32 / MAP_SIZE.x);
 }
 
-fn two_d_index_to_one_d_index(index: UVec2) -> Option<usize> {
+pub fn two_d_index_to_one_d_index(index: UVec2) -> Option<usize> {
     if index.x < MAP_SIZE.x && index.y < MAP_SIZE.y {
         Some((index.y * MAP_SIZE.x + index.x) as usize)
     } else {
@@ -420,9 +800,12 @@ fn change_selected_z_level(
     let mut selected_z_level = selected_z_level_q.single_mut();
     let building_tile_map = building_type_tile_index_map_q.single();
 
-    if keyboard_input.just_pressed(KeyCode::BracketRight) {
+    // the world is only generated from the surface down to the lowest level
+    if keyboard_input.just_pressed(KeyCode::BracketRight) && selected_z_level.0 < SURFACE_Z_LEVEL {
         selected_z_level.0 += 1;
-    } else if keyboard_input.just_pressed(KeyCode::BracketLeft) {
+    } else if keyboard_input.just_pressed(KeyCode::BracketLeft)
+        && selected_z_level.0 > LOWEST_Z_LEVEL
+    {
         selected_z_level.0 -= 1;
     } else {
         // no change return early
@@ -442,34 +825,10 @@ fn change_selected_z_level(
         .unwrap();
     tilemap.fill_rect(&mut commands, fill_area, &TileBuilder::new(tile_empty));
 
-    // try to find the buildings with the matching z level
-    let mut found_z_layer = false;
-    for z_level in z_level_q.iter() {
-        if z_level.z_level != selected_z_level.0 {
-            continue;
-        }
-
-        found_z_layer = true;
-
-        for i_tile in 0..z_level.tiles.len() {
-            let building_type = z_level.tiles[i_tile].building;
-            let tile = building_tile_map.0.get(&building_type).cloned().unwrap();
-
-            tilemap.set(
-                &mut commands,
-                one_d_index_to_two_d_index(i_tile),
-                &TileBuilder::new(tile).with_color(NORMAL_COLOR),
-            );
-        }
-    }
-
-    // if we didn't find an existing z layer make one
-    if !found_z_layer {
-        let mut tiles = Vec::new();
-        tiles.resize(
-            MAP_SIZE.x as usize * MAP_SIZE.y as usize,
-            TileState::default(),
-        );
-        commands.spawn(ZLevel::with_level(selected_z_level.0));
+    if let Some(z_level) = z_level_q
+        .iter()
+        .find(|z_level| z_level.z_level == selected_z_level.0)
+    {
+        draw_z_level(&mut commands, &mut tilemap, z_level, building_tile_map);
     }
 }
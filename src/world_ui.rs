@@ -8,13 +8,15 @@ use bevy::{
     winit::WinitSettings,
 };
 
-use strum::IntoEnumIterator;
-
+use crate::resources::FoodRes;
 use crate::world_map::*;
 
 #[derive(Component)]
 pub struct ZLevelLabel;
 
+#[derive(Component)]
+pub struct FoodLabel;
+
 #[derive(Component)]
 pub struct BuildingTypeButton(BuildingType);
 
@@ -23,7 +25,7 @@ impl Plugin for WorldUIPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(WinitSettings::desktop_app())
         .add_systems(Startup, setup)
-        .add_systems(Update, (building_type_selection_update, update_z_level_ui, button_system));
+        .add_systems(Update, (building_type_selection_update, update_z_level_ui, update_food_ui, button_system));
     }
 }
 
@@ -78,8 +80,26 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                         Label,
                         ZLevelLabel,
                     ));
+
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "Food:",
+                            TextStyle {
+                                font_size: 30.0,
+                                color: Color::rgb(0.1, 0.1, 0.1),
+                                ..default()
+                            },
+                        )
+                        .with_style(Style {
+                            margin: UiRect::all(Val::Px(5.)),
+                            align_self: AlignSelf::Start,
+                            ..default()
+                        }),
+                        Label,
+                        FoodLabel,
+                    ));
                     
-                    for building_type in BuildingType::iter() {
+                    for building_type in PLAYER_BUILDINGS {
                         parent.spawn(
                             (ButtonBundle {
                                 style: Style {
@@ -124,6 +144,13 @@ fn update_z_level_ui(
     }
 }
 
+fn update_food_ui(food: Res<FoodRes>, mut food_label_q: Query<&mut Text, With<FoodLabel>>) {
+    let mut label = food_label_q.single_mut();
+    if let Some(text) = label.sections.first_mut() {
+        text.value = format!("Food:{}", food.amount());
+    }
+}
+
 fn button_system(
     mut interaction_query: Query<
         (
@@ -0,0 +1,166 @@
+use bevy::prelude::*;
+use bevy_prng::ChaCha8Rng;
+use bevy_rand::prelude::*;
+use rand_core::RngCore;
+
+use crate::behavior::spawn_food;
+use crate::flow_field::FlowFields;
+use crate::pheromone::PheromoneField;
+use crate::world_map::*;
+
+/// Patches of one kind of terrain scattered through every level underground, `count` of
+/// them plus `extra_per_depth` more for each level further down.
+struct Pockets {
+    terrain: Terrain,
+    count: u32,
+    extra_per_depth: u32,
+    min_radius: f32, // in tiles
+    max_radius: f32,
+}
+
+// Later pockets are laid over earlier ones
+const POCKETS: [Pockets; 3] = [
+    Pockets {
+        terrain: Terrain::Clay,
+        count: 6,
+        extra_per_depth: 0,
+        min_radius: 2.0,
+        max_radius: 5.0,
+    },
+    Pockets {
+        terrain: Terrain::Rock,
+        count: 2,
+        extra_per_depth: 2,
+        min_radius: 1.5,
+        max_radius: 4.0,
+    },
+    Pockets {
+        terrain: Terrain::Water,
+        count: 1,
+        extra_per_depth: 1,
+        min_radius: 1.0,
+        max_radius: 3.0,
+    },
+];
+// How ragged pocket edges are, as a fraction of the radius
+const POCKET_ROUGHNESS: f32 = 0.3;
+// Tiles around the starting nests kept free of pockets so both colonies can expand
+const NEST_CLEARANCE: u32 = 3;
+
+const SURFACE_FOOD_SOURCES: u32 = 8;
+// Food in the smallest and largest source
+const SURFACE_FOOD_AMOUNT: (u32, u32) = (50, 150);
+// Tiles food keeps away from the map edges and from the starting entrances
+const FOOD_EDGE_MARGIN: f32 = 2.0;
+const FOOD_MIN_ENTRANCE_DISTANCE: f32 = 6.0;
+
+// What the undug ground of a level is mostly made of, by how many levels it is below
+// the surface
+fn soil_layer(depth: i32) -> Terrain {
+    match depth {
+        1 => Terrain::Loam,
+        2 => Terrain::Soil,
+        _ => Terrain::Clay,
+    }
+}
+
+fn roll(rng: &mut EntropyComponent<ChaCha8Rng>) -> f32 {
+    rng.next_u32() as f32 / u32::MAX as f32
+}
+
+// Builds every level from the surface down to `LOWEST_Z_LEVEL`, digs out the starting
+// nests and scatters food over the surface. Everything comes from the global entropy
+// source, so the same seed always makes the same world.
+pub fn generate_world(mut commands: Commands, mut global_rng: ResMut<GlobalEntropy<ChaCha8Rng>>) {
+    let mut rng = global_rng.fork_rng();
+    let entrances = [STARTING_ENTRANCE, RIVAL_NEST_ENTRANCE];
+
+    for level in (LOWEST_Z_LEVEL..SURFACE_Z_LEVEL).rev() {
+        let base = soil_layer(SURFACE_Z_LEVEL - level);
+        let mut z_level = ZLevel::with_level(level);
+        set_terrain(
+            &mut z_level,
+            URect::from_corners(UVec2::ZERO, MAP_SIZE),
+            base,
+        );
+        add_pockets(&mut z_level, &mut rng, SURFACE_Z_LEVEL - level);
+
+        if level == SURFACE_Z_LEVEL - 1 {
+            for area in starting_nest_areas(NEST_CLEARANCE) {
+                set_terrain(&mut z_level, area, base);
+            }
+            carve_starting_nests(&mut z_level);
+            for entrance in entrances {
+                z_level[entrance].building = BuildingType::Entrance;
+            }
+        }
+        commands.spawn((z_level, PheromoneField::default(), FlowFields::default()));
+    }
+
+    let mut surface = ZLevel::with_level(SURFACE_Z_LEVEL);
+    surface.set_area(
+        URect::from_corners(UVec2::ZERO, MAP_SIZE),
+        BuildingType::Ground,
+    );
+    for entrance in entrances {
+        surface[entrance].building = BuildingType::Entrance;
+    }
+    commands.spawn((surface, PheromoneField::default(), FlowFields::default()));
+
+    scatter_surface_food(&mut commands, &mut rng, &entrances);
+}
+
+fn set_terrain(z_level: &mut ZLevel, area: URect, terrain: Terrain) {
+    for x in area.min.x..area.max.x {
+        for y in area.min.y..area.max.y {
+            if let Some(i) = two_d_index_to_one_d_index(UVec2::new(x, y)) {
+                z_level.tiles[i].terrain = terrain;
+            }
+        }
+    }
+}
+
+fn add_pockets(z_level: &mut ZLevel, rng: &mut EntropyComponent<ChaCha8Rng>, depth: i32) {
+    for pockets in POCKETS.iter() {
+        let count = pockets.count + pockets.extra_per_depth * (depth - 1).max(0) as u32;
+        for _ in 0..count {
+            let center = Vec2::new(roll(rng), roll(rng)) * MAP_SIZE.as_vec2();
+            let radius = pockets.min_radius + roll(rng) * (pockets.max_radius - pockets.min_radius);
+
+            let min = (center - radius).max(Vec2::ZERO).as_uvec2();
+            let max = (center + radius).ceil().as_uvec2().min(MAP_SIZE);
+            for x in min.x..max.x {
+                for y in min.y..max.y {
+                    let tile = UVec2::new(x, y);
+                    let distance = (tile.as_vec2() + 0.5).distance(center);
+                    if distance <= radius * (1.0 - POCKET_ROUGHNESS * roll(rng)) {
+                        z_level[tile].terrain = pockets.terrain;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn scatter_surface_food(
+    commands: &mut Commands,
+    rng: &mut EntropyComponent<ChaCha8Rng>,
+    entrances: &[UVec2],
+) {
+    let (smallest, largest) = SURFACE_FOOD_AMOUNT;
+    let spread = MAP_SIZE.as_vec2() - 2.0 * FOOD_EDGE_MARGIN;
+
+    let mut placed = 0;
+    while placed < SURFACE_FOOD_SOURCES {
+        let tile_pos = Vec2::splat(FOOD_EDGE_MARGIN) + Vec2::new(roll(rng), roll(rng)) * spread;
+        if entrances.iter().any(|entrance| {
+            (entrance.as_vec2() + 0.5).distance(tile_pos) < FOOD_MIN_ENTRANCE_DISTANCE
+        }) {
+            continue;
+        }
+
+        let amount = smallest + rng.next_u32() % (largest - smallest + 1);
+        spawn_food(commands, tile_pos * TILE_SIZE, SURFACE_Z_LEVEL, amount);
+        placed += 1;
+    }
+}